@@ -0,0 +1,219 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use crate::cell::{ AtomicRefCell, BorrowError, Ref, RefMut };
+use crate::component::{ Component, ComponentId, Components };
+use crate::entity::Entity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArchetypeId(usize);
+
+impl ArchetypeId {
+    // entities without any component
+    pub const EMPTY: ArchetypeId = ArchetypeId(0);
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+// where the components of an entity are stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub archetype: ArchetypeId,
+    pub row: usize
+}
+
+// the values of one component type for every entity of an archetype
+pub(crate) trait Column: Any + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn swap_remove(&mut self, row: usize);
+    // take the value at `row` out and push it onto `other`, a column of the same type
+    fn move_row(&mut self, row: usize, other: &mut dyn Column);
+}
+
+impl<T: Component> Column for Vec<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn swap_remove(&mut self, row: usize) {
+        Vec::swap_remove(self, row);
+    }
+
+    fn move_row(&mut self, row: usize, other: &mut dyn Column) {
+        let value = Vec::swap_remove(self, row);
+        other.as_any_mut().downcast_mut::<Vec<T>>().unwrap().push(value);
+    }
+}
+
+// every entity with exactly the same set of components, one column per component
+// and one row per entity, so iterating over a component set only visits entities that have it
+pub struct Archetype {
+    id: ArchetypeId,
+    // sorted, in the same order as `columns`
+    components: Vec<ComponentId>,
+    columns: Vec<AtomicRefCell<Box<dyn Column>>>,
+    entities: Vec<Entity>
+}
+
+impl Archetype {
+    pub fn id(&self) -> ArchetypeId {
+        self.id
+    }
+
+    pub fn components(&self) -> &[ComponentId] {
+        &self.components
+    }
+
+    // the entity of every row
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn contains(&self, component: ComponentId) -> bool {
+        self.components.binary_search(&component).is_ok()
+    }
+
+    // the values of `component`, none when the archetype does not have it
+    pub fn column<T: Component>(&self, component: ComponentId) -> Option<Ref<'_, Vec<T>>> {
+        let column = self.columns[self.components.binary_search(&component).ok()?].borrow();
+        Ref::filter_map(column, |column| column.as_any().downcast_ref::<Vec<T>>())
+    }
+
+    pub fn column_mut<T: Component>(&self, component: ComponentId) -> Option<RefMut<'_, Vec<T>>> {
+        let column = self.columns[self.components.binary_search(&component).ok()?].borrow_mut();
+        RefMut::filter_map(column, |column| column.as_any_mut().downcast_mut::<Vec<T>>())
+    }
+
+    // like `column`, an error instead of a panic when the column is borrowed mutably elsewhere
+    pub(crate) fn try_column<T: Component>(&self, component: ComponentId) -> Result<Option<Ref<'_, Vec<T>>>, BorrowError> {
+        let column = match self.components.binary_search(&component) {
+            Ok(index) => self.columns[index].try_borrow()?,
+            Err(_) => return Ok(None),
+        };
+        Ok(Ref::filter_map(column, |column| column.as_any().downcast_ref::<Vec<T>>()))
+    }
+
+    pub(crate) fn try_column_mut<T: Component>(
+        &self,
+        component: ComponentId
+    ) -> Result<Option<RefMut<'_, Vec<T>>>, BorrowError> {
+        let column = match self.components.binary_search(&component) {
+            Ok(index) => self.columns[index].try_borrow_mut()?,
+            Err(_) => return Ok(None),
+        };
+        Ok(RefMut::filter_map(column, |column| column.as_any_mut().downcast_mut::<Vec<T>>()))
+    }
+
+    pub(crate) fn typed_column<T: Component>(&mut self, component: ComponentId) -> &mut Vec<T> {
+        let index = self.components.binary_search(&component).unwrap();
+        self.columns[index].get_mut().as_any_mut().downcast_mut::<Vec<T>>().unwrap()
+    }
+
+    pub(crate) fn push_entity(&mut self, entity: Entity) -> usize {
+        self.entities.push(entity);
+        self.entities.len() - 1
+    }
+
+    // drop the components at `row`, returns the entity that took its place
+    pub(crate) fn swap_remove(&mut self, row: usize) -> Option<Entity> {
+        for column in self.columns.iter_mut() {
+            column.get_mut().swap_remove(row);
+        }
+        self.entities.swap_remove(row);
+
+        self.entities.get(row).cloned()
+    }
+
+    // move the entity at `row` to `target`, the components `target` has no column for are dropped
+    // except for `taken`, whose value was already taken out of its column;
+    // returns the new row and the entity that took the old one
+    pub(crate) fn move_row(
+        &mut self,
+        row: usize,
+        target: &mut Archetype,
+        taken: Option<ComponentId>
+    ) -> (usize, Option<Entity>) {
+        for (component, column) in self.components.iter().zip(self.columns.iter_mut()) {
+            if Some(*component) == taken {
+                continue;
+            }
+            match target.components.binary_search(component) {
+                Ok(index) => column.get_mut().move_row(row, target.columns[index].get_mut().as_mut()),
+                Err(_) => column.get_mut().swap_remove(row),
+            }
+        }
+        let entity = self.entities.swap_remove(row);
+
+        (target.push_entity(entity), self.entities.get(row).cloned())
+    }
+}
+
+// every archetype of a world, looked up by its sorted component set
+pub struct Archetypes {
+    archetypes: Vec<Archetype>,
+    ids: HashMap<Vec<ComponentId>, ArchetypeId>
+}
+
+impl Default for Archetypes {
+    fn default() -> Self {
+        let mut archetypes = Archetypes { archetypes: Vec::new(), ids: HashMap::new() };
+        archetypes.get_or_insert(vec![], &Components::default());
+        archetypes
+    }
+}
+
+impl Archetypes {
+    // the archetype of exactly `components`, which must be sorted
+    pub fn get_or_insert(&mut self, components: Vec<ComponentId>, registry: &Components) -> ArchetypeId {
+        if let Some(&id) = self.ids.get(&components) {
+            return id;
+        }
+
+        let id = ArchetypeId(self.archetypes.len());
+        let columns = components.iter()
+            .map(|&component| AtomicRefCell::new((registry.info(component).new_column)()))
+            .collect();
+        self.archetypes.push(Archetype { id, components: components.clone(), columns, entities: Vec::new() });
+        self.ids.insert(components, id);
+        id
+    }
+
+    pub fn get(&self, id: ArchetypeId) -> &Archetype {
+        &self.archetypes[id.index()]
+    }
+
+    pub fn get_mut(&mut self, id: ArchetypeId) -> &mut Archetype {
+        &mut self.archetypes[id.index()]
+    }
+
+    // two different archetypes at once, to move an entity from one to the other
+    pub fn pair_mut(&mut self, a: ArchetypeId, b: ArchetypeId) -> (&mut Archetype, &mut Archetype) {
+        assert_ne!(a, b, "an entity only moves between different archetypes");
+        if a.index() < b.index() {
+            let (left, right) = self.archetypes.split_at_mut(b.index());
+            (&mut left[a.index()], &mut right[0])
+        } else {
+            let (left, right) = self.archetypes.split_at_mut(a.index());
+            (&mut right[0], &mut left[b.index()])
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Archetype> {
+        self.archetypes.iter()
+    }
+}
@@ -0,0 +1,237 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{ Deref, DerefMut };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+// `borrows` of a cell borrowed mutably
+const EXCLUSIVE: usize = usize::MAX;
+
+// a `RefCell` that can be shared between threads,
+// a borrow that would break the borrow rules fails instead of waiting
+pub struct AtomicRefCell<T: ?Sized> {
+    // number of shared borrows, or `EXCLUSIVE`
+    borrows: AtomicUsize,
+    value: UnsafeCell<T>
+}
+
+// the borrows are tracked like a `RwLock` does, so sharing is as safe as for one
+unsafe impl<T: ?Sized + Send + Sync> Sync for AtomicRefCell<T> {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BorrowError;
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "already borrowed")
+    }
+}
+
+impl<T> AtomicRefCell<T> {
+    pub fn new(value: T) -> Self {
+        AtomicRefCell { borrows: AtomicUsize::new(0), value: UnsafeCell::new(value) }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> AtomicRefCell<T> {
+    pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+        let mut borrows = self.borrows.load(Ordering::Relaxed);
+        loop {
+            if borrows >= EXCLUSIVE - 1 {
+                return Err(BorrowError);
+            }
+            match self.borrows.compare_exchange_weak(borrows, borrows + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => borrows = current,
+            }
+        }
+
+        // no `RefMut` exists until the count goes back to zero
+        let value = unsafe { &*self.value.get() };
+        Ok(Ref { value, release: Release { borrows: &self.borrows, exclusive: false } })
+    }
+
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowError> {
+        if self.borrows.compare_exchange(0, EXCLUSIVE, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return Err(BorrowError);
+        }
+
+        // no other `Ref` or `RefMut` exists until the count goes back to zero
+        let value = unsafe { &mut *self.value.get() };
+        Ok(RefMut { value, release: Release { borrows: &self.borrows, exclusive: true } })
+    }
+
+    // panics when it is borrowed mutably
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.try_borrow().expect("already mutably borrowed")
+    }
+
+    // panics when it is borrowed at all
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.try_borrow_mut().expect("already borrowed")
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+// gives the borrow back when the guard holding it is dropped
+struct Release<'a> {
+    borrows: &'a AtomicUsize,
+    exclusive: bool
+}
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        match self.exclusive {
+            true => self.borrows.store(0, Ordering::Release),
+            false => { self.borrows.fetch_sub(1, Ordering::Release); },
+        }
+    }
+}
+
+// a shared borrow of an `AtomicRefCell`
+pub struct Ref<'a, T: ?Sized> {
+    value: &'a T,
+    release: Release<'a>
+}
+
+impl<'a, T: ?Sized> Ref<'a, T> {
+    pub fn map<U: ?Sized>(orig: Ref<'a, T>, f: impl FnOnce(&T) -> &U) -> Ref<'a, U> {
+        let Ref { value, release } = orig;
+        Ref { value: f(value), release }
+    }
+
+    // none, giving the borrow back, when `f` finds nothing
+    pub fn filter_map<U: ?Sized>(orig: Ref<'a, T>, f: impl FnOnce(&T) -> Option<&U>) -> Option<Ref<'a, U>> {
+        let Ref { value, release } = orig;
+        Some(Ref { value: f(value)?, release })
+    }
+}
+
+impl<T: ?Sized> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+// an exclusive borrow of an `AtomicRefCell`
+pub struct RefMut<'a, T: ?Sized> {
+    value: &'a mut T,
+    release: Release<'a>
+}
+
+impl<'a, T: ?Sized> RefMut<'a, T> {
+    pub fn map<U: ?Sized>(orig: RefMut<'a, T>, f: impl FnOnce(&mut T) -> &mut U) -> RefMut<'a, U> {
+        let RefMut { value, release } = orig;
+        RefMut { value: f(value), release }
+    }
+
+    // none, giving the borrow back, when `f` finds nothing
+    pub fn filter_map<U: ?Sized>(
+        orig: RefMut<'a, T>,
+        f: impl FnOnce(&mut T) -> Option<&mut U>
+    ) -> Option<RefMut<'a, U>> {
+        let RefMut { value, release } = orig;
+        Some(RefMut { value: f(value)?, release })
+    }
+}
+
+impl<T: ?Sized> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn shared_borrows_coexist() {
+        let cell = AtomicRefCell::new(5);
+        let a = cell.try_borrow().unwrap();
+        let b = cell.try_borrow().unwrap();
+
+        assert_eq!(*a + *b, 10);
+        assert_eq!(cell.try_borrow_mut().err(), Some(BorrowError));
+    }
+
+    #[test]
+    fn mutable_borrow_excludes_every_other() {
+        let cell = AtomicRefCell::new(5);
+        let mut value = cell.try_borrow_mut().unwrap();
+        *value += 1;
+
+        assert!(cell.try_borrow().is_err());
+        assert!(cell.try_borrow_mut().is_err());
+        drop(value);
+        assert_eq!(*cell.borrow(), 6);
+    }
+
+    #[test]
+    fn borrows_are_given_back_on_drop() {
+        let cell = AtomicRefCell::new(vec![1, 2, 3]);
+        drop((cell.borrow(), cell.borrow()));
+        drop(cell.borrow_mut());
+
+        // a mapped guard keeps the borrow of the whole cell
+        let first = Ref::map(cell.borrow(), |values| &values[0]);
+        assert!(cell.try_borrow_mut().is_err());
+        drop(first);
+        let first = RefMut::map(cell.borrow_mut(), |values| &mut values[0]);
+        assert!(cell.try_borrow().is_err());
+        drop(first);
+
+        // and so does one that found nothing, until it is dropped
+        assert!(Ref::filter_map(cell.borrow(), |values| values.get(3)).is_none());
+        assert!(cell.try_borrow_mut().is_ok());
+        assert!(RefMut::filter_map(cell.borrow_mut(), |values| values.get_mut(3)).is_none());
+        assert!(cell.try_borrow().is_ok());
+        assert_eq!(cell.into_inner(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn mutable_borrows_are_exclusive_across_threads() {
+        let cell = Arc::new(AtomicRefCell::new(0usize));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let cell = Arc::clone(&cell);
+                thread::spawn(move || {
+                    let mut written = 0;
+                    for _ in 0..10_000 {
+                        if let Ok(mut value) = cell.try_borrow_mut() {
+                            // a lost update would show if another thread got in
+                            let read = *value;
+                            *value = read + 1;
+                            written += 1;
+                        }
+                        let _ = cell.try_borrow();
+                    }
+                    written
+                })
+            })
+            .collect();
+
+        let written: usize = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+        assert_eq!(*cell.borrow(), written);
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+}
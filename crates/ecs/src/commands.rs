@@ -0,0 +1,140 @@
+use std::mem;
+use std::sync::Mutex;
+
+use crate::component::Component;
+use crate::entity::{ Entities, Entity };
+use crate::world::{ EcsError, World };
+
+type Command = Box<dyn FnOnce(&mut World) -> Result<(), EcsError> + Send>;
+
+// the changes recorded by `Commands`, waiting for `apply`
+#[derive(Default)]
+pub struct CommandQueue {
+    commands: Mutex<Vec<Command>>
+}
+
+impl CommandQueue {
+    // the sync point, the commands run in the order they were recorded,
+    // one that fails does not stop the others and the first error is returned
+    pub fn apply(&mut self, world: &mut World) -> Result<(), EcsError> {
+        world.flush_entities();
+        let commands = mem::take(self.commands.get_mut().unwrap());
+        let mut result = Ok(());
+        for command in commands {
+            if let Err(err) = command(world) {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&self, command: impl FnOnce(&mut World) -> Result<(), EcsError> + Send + 'static) {
+        self.commands.lock().unwrap().push(Box::new(command));
+    }
+}
+
+// records the structural changes that cannot be made while the world is borrowed,
+// during a query for example, a copy can be sent to every thread
+//
+// as a system parameter, its commands are applied after the stage of the system
+#[derive(Clone, Copy)]
+pub struct Commands<'w> {
+    queue: &'w CommandQueue,
+    entities: &'w Entities
+}
+
+impl<'w> Commands<'w> {
+    pub fn new(queue: &'w CommandQueue, world: &'w World) -> Self {
+        Commands { queue, entities: world.entities() }
+    }
+
+    // a handle usable right away, the entity is alive after the next `apply`
+    pub fn spawn(&self) -> Entity {
+        self.entities.reserve()
+    }
+
+    // nothing happens to an entity already despawned
+    pub fn despawn(&self, entity: Entity) {
+        self.queue.push(move |world| {
+            world.despawn(entity);
+            Ok(())
+        });
+    }
+
+    pub fn insert<CT: Component>(&self, entity: Entity, component: CT) {
+        self.queue.push(move |world| world.add_component_to_entity(entity, component));
+    }
+
+    pub fn remove<CT: Component>(&self, entity: Entity) {
+        self.queue.push(move |world| world.remove_component_from_entity::<CT>(entity).map(drop));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Label(usize);
+
+    #[test]
+    fn entities_spawned_from_several_threads_are_all_different() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut spawned: Vec<Entity> = {
+            let commands = Commands::new(&queue, &world);
+            thread::scope(|scope| {
+                let threads: Vec<_> = (0..4)
+                    .map(|_| scope.spawn(move || {
+                        (0..50).map(|i| {
+                            let entity = commands.spawn();
+                            commands.insert(entity, Label(i));
+                            entity
+                        }).collect::<Vec<_>>()
+                    }))
+                    .collect();
+                threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect()
+            })
+        };
+        assert_eq!(queue.len(), 200);
+        assert!(spawned.iter().all(|&entity| !world.is_alive(entity)));
+
+        queue.apply(&mut world).unwrap();
+        assert!(queue.is_empty());
+        assert!(spawned.iter().all(|&entity| world.is_alive(entity) && world.component::<Label>(entity).is_some()));
+        spawned.sort_by_key(|entity| entity.index());
+        spawned.dedup();
+        assert_eq!(spawned.len(), 200);
+        assert_eq!(world.new_entity().index(), 200);
+    }
+
+    #[test]
+    fn failed_command_does_not_stop_the_others() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let kept = world.new_entity();
+        let gone = world.new_entity();
+        world.add_component_to_entity(kept, Label(0)).unwrap();
+
+        let commands = Commands::new(&queue, &world);
+        commands.despawn(gone);
+        commands.insert(gone, Label(1));
+        commands.remove::<Label>(kept);
+        commands.despawn(gone);
+        commands.insert(kept, Label(2));
+
+        assert_eq!(queue.apply(&mut world), Err(EcsError::NoSuchEntity(gone)));
+        assert!(!world.is_alive(gone));
+        assert_eq!(*world.component::<Label>(kept).unwrap(), Label(2));
+    }
+}
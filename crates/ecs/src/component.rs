@@ -0,0 +1,87 @@
+use std::any::{ self, TypeId };
+use std::collections::HashMap;
+
+use crate::archetype::Column;
+use crate::sparse::{ SparseSet, SparseStorage };
+
+// data attached to entities, shared with the systems running on other threads
+pub trait Component: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Component for T {}
+
+// dense number of a component type, storages are looked up by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ComponentId(usize);
+
+impl ComponentId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+// where the values of a component type live
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageType {
+    // columns of the archetype tables, fastest to iterate
+    Table,
+    // a sparse set next to the tables, for components added and removed all the time
+    SparseSet
+}
+
+#[derive(Debug)]
+pub struct ComponentInfo {
+    pub id: ComponentId,
+    pub name: &'static str,
+    pub type_id: TypeId,
+    pub storage: StorageType,
+    // empty storages for the type
+    pub(crate) new_column: fn() -> Box<dyn Column>,
+    pub(crate) new_sparse_set: fn() -> Box<dyn SparseStorage>
+}
+
+// numbers component types in the order they are first seen
+#[derive(Default)]
+pub struct Components {
+    ids: HashMap<TypeId, ComponentId>,
+    infos: Vec<ComponentInfo>
+}
+
+impl Components {
+    // the id of `T`, a new one stored in tables the first time
+    pub fn register<T: Component>(&mut self) -> ComponentId {
+        self.register_with::<T>(StorageType::Table)
+    }
+
+    // the id of `T`, `storage` only counts the first time
+    pub fn register_with<T: Component>(&mut self, storage: StorageType) -> ComponentId {
+        let infos = &mut self.infos;
+        *self.ids.entry(TypeId::of::<T>()).or_insert_with(|| {
+            let id = ComponentId(infos.len());
+            infos.push(ComponentInfo {
+                id,
+                name: any::type_name::<T>(),
+                type_id: TypeId::of::<T>(),
+                storage,
+                new_column: || Box::new(Vec::<T>::new()),
+                new_sparse_set: || Box::new(SparseSet::<T>::default())
+            });
+            id
+        })
+    }
+
+    pub fn id<T: 'static>(&self) -> Option<ComponentId> {
+        self.ids.get(&TypeId::of::<T>()).cloned()
+    }
+
+    pub fn info(&self, id: ComponentId) -> &ComponentInfo {
+        &self.infos[id.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.infos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.infos.is_empty()
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{ AtomicU32, Ordering };
+
+// handle to an entity, the generation tells apart the entities that reused the same slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32
+}
+
+impl Entity {
+    // slot of the entity in every component vec
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+// hands out entity slots, a despawned slot goes on the free list
+// and comes back with the next generation
+#[derive(Default)]
+pub struct Entities {
+    // current generation of every slot, bumped when its entity is despawned
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    // slots handed out by `reserve` after the last one of `generations`, not allocated yet
+    reserved: AtomicU32
+}
+
+impl Entities {
+    // `flush` the reserved entities first, or a slot is handed out twice
+    pub fn alloc(&mut self) -> Entity {
+        debug_assert_eq!(*self.reserved.get_mut(), 0);
+        match self.free.pop() {
+            Some(index) => Entity { index, generation: self.generations[index as usize] },
+            None => {
+                self.generations.push(0);
+                Entity { index: self.generations.len() as u32 - 1, generation: 0 }
+            },
+        }
+    }
+
+    // false when the entity was already despawned
+    pub fn free(&mut self, entity: Entity) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+
+        let generation = &mut self.generations[entity.index()];
+        *generation = generation.wrapping_add(1);
+        self.free.push(entity.index);
+        true
+    }
+
+    // an entity allocated later by `flush`, from any thread,
+    // always a new slot since the free list cannot be shared
+    pub fn reserve(&self) -> Entity {
+        let index = self.generations.len() as u32 + self.reserved.fetch_add(1, Ordering::Relaxed);
+        Entity { index, generation: 0 }
+    }
+
+    // allocate the reserved entities, in the order they were reserved
+    pub fn flush(&mut self) -> impl Iterator<Item = Entity> {
+        let start = self.generations.len() as u32;
+        let reserved = mem::take(self.reserved.get_mut());
+        self.generations.resize((start + reserved) as usize, 0);
+        (start..start + reserved).map(|index| Entity { index, generation: 0 })
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index()) == Some(&entity.generation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freed_slots_come_back_with_the_next_generation() {
+        let mut entities = Entities::default();
+        let a = entities.alloc();
+        let b = entities.alloc();
+        assert_eq!((a.index(), b.index()), (0, 1));
+        assert_eq!((a.generation(), b.generation()), (0, 0));
+
+        assert!(entities.free(a));
+        assert!(!entities.free(a));
+        assert!(!entities.contains(a));
+        assert!(entities.contains(b));
+
+        let c = entities.alloc();
+        assert_eq!((c.index(), c.generation()), (0, 1));
+        assert_ne!(a, c);
+        assert!(entities.contains(c));
+        assert_eq!(entities.alloc().index(), 2);
+    }
+
+    #[test]
+    fn reserved_entities_are_allocated_by_flush() {
+        let mut entities = Entities::default();
+        let a = entities.alloc();
+        entities.free(a);
+
+        // the freed slot is not reused by `reserve`
+        let reserved = [entities.reserve(), entities.reserve()];
+        assert_eq!(reserved.map(|entity| entity.index()), [1, 2]);
+        assert!(!entities.contains(reserved[0]));
+
+        assert_eq!(entities.flush().collect::<Vec<_>>(), reserved);
+        assert!(reserved.iter().all(|&entity| entities.contains(entity)));
+        assert_eq!(entities.flush().count(), 0);
+        assert_eq!(entities.alloc().index(), 0);
+        assert_eq!(entities.alloc().index(), 3);
+    }
+}
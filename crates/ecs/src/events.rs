@@ -0,0 +1,142 @@
+use std::mem;
+
+use crate::cell::{ Ref, RefMut };
+
+// a message from one system to the others, sent through `Events`
+pub trait Event: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Event for T {}
+
+// the events of the last two ticks, double buffered so every reader sees an event
+// whether it runs before or after the writer, a world resource
+pub struct Events<E> {
+    // sent during the tick before
+    previous: Vec<E>,
+    // sent during this tick
+    current: Vec<E>,
+    // id of the first event of `previous`, ids count every event ever sent
+    start: usize,
+    count: usize
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Events { previous: Vec::new(), current: Vec::new(), start: 0, count: 0 }
+    }
+}
+
+impl<E> Events<E> {
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+        self.count += 1;
+    }
+
+    // at the end of every tick, drops the events of the tick before
+    pub fn update(&mut self) {
+        self.previous = mem::take(&mut self.current);
+        self.start = self.count - self.previous.len();
+    }
+
+    // the events still kept
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the events kept with an id of at least `id`
+    fn since(&self, id: usize) -> impl Iterator<Item = &E> {
+        self.previous.iter().chain(self.current.iter()).skip(id.saturating_sub(self.start))
+    }
+}
+
+// system parameter sending events of type `E`
+pub struct EventWriter<'w, E: Event> {
+    events: RefMut<'w, Events<E>>
+}
+
+impl<'w, E: Event> EventWriter<'w, E> {
+    pub(crate) fn new(events: RefMut<'w, Events<E>>) -> Self {
+        EventWriter { events }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+}
+
+// system parameter reading the events of type `E`, each system sees every event once
+pub struct EventReader<'w, E: Event> {
+    // id of the next event the system has not read, kept by the system between runs
+    cursor: &'w mut usize,
+    events: Ref<'w, Events<E>>
+}
+
+impl<'w, E: Event> EventReader<'w, E> {
+    pub(crate) fn new(cursor: &'w mut usize, events: Ref<'w, Events<E>>) -> Self {
+        EventReader { cursor, events }
+    }
+
+    // the events sent since the last time, the ones older than the tick before are lost
+    pub fn iter(&mut self) -> impl Iterator<Item = &E> {
+        let cursor = mem::replace(self.cursor, self.events.count);
+        self.events.since(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    #[derive(Debug, PartialEq)]
+    struct Hit(u32);
+
+    fn read(world: &World, cursor: &mut usize) -> Vec<u32> {
+        let mut reader = EventReader::new(cursor, world.resource::<Events<Hit>>().unwrap());
+        reader.iter().map(|hit| hit.0).collect()
+    }
+
+    fn send(world: &World, hit: u32) {
+        EventWriter::new(world.resource_mut::<Events<Hit>>().unwrap()).send(Hit(hit));
+    }
+
+    #[test]
+    fn every_reader_sees_an_event_once() {
+        let mut world = World::new();
+        world.add_event::<Hit>();
+        let (mut early, mut late) = (0, 0);
+
+        // `early` reads before the writer of the tick, `late` after it
+        assert!(read(&world, &mut early).is_empty());
+        send(&world, 1);
+        send(&world, 2);
+        assert_eq!(read(&world, &mut late), [1, 2]);
+        assert!(read(&world, &mut late).is_empty());
+        world.update_events();
+
+        assert_eq!(read(&world, &mut early), [1, 2]);
+        send(&world, 3);
+        assert_eq!(read(&world, &mut late), [3]);
+        assert_eq!(read(&world, &mut early), [3]);
+    }
+
+    #[test]
+    fn events_are_dropped_after_two_updates() {
+        let mut events = Events::default();
+        events.send(Hit(1));
+        events.update();
+        events.send(Hit(2));
+        assert_eq!(events.len(), 2);
+        events.update();
+        assert_eq!(events.since(0).collect::<Vec<_>>(), [&Hit(2)]);
+        events.update();
+        assert!(events.is_empty());
+
+        // a reader that fell behind only gets what is left
+        events.send(Hit(3));
+        assert_eq!(events.since(0).collect::<Vec<_>>(), [&Hit(3)]);
+    }
+}
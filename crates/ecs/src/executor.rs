@@ -0,0 +1,329 @@
+use std::any::Any;
+use std::panic::{ self, AssertUnwindSafe };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::sync::Mutex;
+use std::thread;
+
+use rayon::{ Scope, ThreadPool, ThreadPoolBuilder };
+
+use crate::query::Access;
+use crate::system::System;
+use crate::world::{ EcsError, World };
+
+// runs the systems of a stage, `order` sorts them by their dependencies
+// and `after[i]` are the systems that must be done before `systems[i]` starts
+pub trait Executor {
+    fn run(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        order: &[usize],
+        after: &[Vec<usize>],
+        world: &World
+    ) -> Result<(), EcsError>;
+}
+
+// one system after the other, on the calling thread
+#[derive(Default)]
+pub struct SingleThreadedExecutor;
+
+impl Executor for SingleThreadedExecutor {
+    fn run(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        order: &[usize],
+        _: &[Vec<usize>],
+        world: &World
+    ) -> Result<(), EcsError> {
+        for &system in order {
+            systems[system].run(world)?;
+        }
+        Ok(())
+    }
+}
+
+// the systems that neither depend on each other nor access a component in conflicting ways
+// run at the same time on a pool of threads, the others in the order of the stage;
+// the threads are started once with the executor and wait for the next stage between runs
+pub struct ParallelExecutor {
+    pool: ThreadPool
+}
+
+impl Default for ParallelExecutor {
+    fn default() -> Self {
+        ParallelExecutor::new(thread::available_parallelism().map_or(1, |threads| threads.get()))
+    }
+}
+
+impl ParallelExecutor {
+    pub fn new(threads: usize) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .thread_name(|index| format!("ecs-executor-{}", index))
+            .build()
+            .expect("failed to start the executor threads");
+
+        ParallelExecutor { pool }
+    }
+}
+
+// a system that may access anything conflicts with every other one
+fn conflicts(a: &Option<Vec<Access>>, b: &Option<Vec<Access>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.iter().any(|a| b.iter().any(|b| a.conflicts(b))),
+        _ => true,
+    }
+}
+
+type Outcome = Result<Result<(), EcsError>, Box<dyn Any + Send>>;
+
+// one stage being run, shared by the systems running on the pool
+struct Run<'a> {
+    world: &'a World,
+    // taken by the thread that runs the system
+    systems: Vec<Mutex<Option<&'a mut Box<dyn System>>>>,
+    successors: Vec<Vec<usize>>,
+    // systems each one still waits for
+    waiting: Vec<AtomicUsize>,
+    // the first error or panic, nothing new starts once there is one
+    failure: Mutex<Option<Outcome>>
+}
+
+impl<'a> Run<'a> {
+    // runs `system` on the pool, the last of its predecessors to finish starts a successor
+    fn start<'s>(&'s self, scope: &Scope<'s>, system: usize) {
+        scope.spawn(move |scope| {
+            let taken = self.systems[system].lock().unwrap().take().unwrap();
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| taken.run(self.world)));
+            match outcome {
+                Ok(Ok(())) => {
+                    for &successor in self.successors[system].iter() {
+                        let ready = self.waiting[successor].fetch_sub(1, Ordering::AcqRel) == 1;
+                        if ready && self.failure.lock().unwrap().is_none() {
+                            self.start(scope, successor);
+                        }
+                    }
+                },
+                outcome => { self.failure.lock().unwrap().get_or_insert(outcome); },
+            }
+        });
+    }
+}
+
+impl Executor for ParallelExecutor {
+    fn run(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        order: &[usize],
+        after: &[Vec<usize>],
+        world: &World
+    ) -> Result<(), EcsError> {
+        // an edge from every system to the later ones that have to wait for it
+        let access: Vec<_> = systems.iter().map(|system| system.access()).collect();
+        let mut successors = vec![Vec::new(); systems.len()];
+        let mut waiting = vec![0; systems.len()];
+        for (k, &i) in order.iter().enumerate() {
+            for &j in order[..k].iter() {
+                if after[i].contains(&j) || conflicts(&access[i], &access[j]) {
+                    successors[j].push(i);
+                    waiting[i] += 1;
+                }
+            }
+        }
+
+        let run = Run {
+            world,
+            systems: systems.iter_mut().map(|system| Mutex::new(Some(system))).collect(),
+            successors,
+            waiting: waiting.iter().map(|&waiting| AtomicUsize::new(waiting)).collect(),
+            failure: Mutex::new(None)
+        };
+        // returns once every system started is done
+        self.pool.scope(|scope| {
+            for &i in order.iter().filter(|&&i| waiting[i] == 0) {
+                run.start(scope, i);
+            }
+        });
+
+        match run.failure.into_inner().unwrap() {
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            Some(Ok(result)) => result,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::time::{ Duration, Instant };
+
+    use super::*;
+
+    struct A;
+
+    type Work = Box<dyn FnMut() -> Result<(), EcsError> + Send>;
+
+    // a system that only does `work`, with the access it is given
+    struct Probe {
+        access: Option<Vec<Access>>,
+        work: Work
+    }
+
+    impl System for Probe {
+        fn name(&self) -> &'static str {
+            "probe"
+        }
+
+        fn access(&self) -> Option<Vec<Access>> {
+            self.access.clone()
+        }
+
+        fn run(&mut self, _: &World) -> Result<(), EcsError> {
+            (self.work)()
+        }
+    }
+
+    fn probe(access: Option<Vec<Access>>, work: impl FnMut() -> Result<(), EcsError> + Send + 'static) -> Box<dyn System> {
+        Box::new(Probe { access, work: Box::new(work) })
+    }
+
+    fn run(executor: &mut ParallelExecutor, systems: &mut [Box<dyn System>], after: &[Vec<usize>]) -> Result<(), EcsError> {
+        let order: Vec<usize> = (0..systems.len()).collect();
+        executor.run(systems, &order, after, &World::new())
+    }
+
+    #[test]
+    fn conflicting_systems_never_overlap() {
+        let inside = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let mut systems: Vec<_> = (0..6)
+            .map(|i| {
+                let (inside, most) = (Arc::clone(&inside), Arc::clone(&most));
+                // every other one may touch anything, the rest all write `A`
+                let access = if i % 2 == 0 { Some(vec![Access::write::<A>()]) } else { None };
+                probe(access, move || {
+                    most.fetch_max(inside.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    inside.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                })
+            })
+            .collect();
+
+        run(&mut ParallelExecutor::new(4), &mut systems, &vec![vec![]; 6]).unwrap();
+        assert_eq!(most.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn readers_run_at_the_same_time() {
+        // each reader waits for the other one to have started
+        let arrived = Arc::new(AtomicUsize::new(0));
+        let met = Arc::new(AtomicUsize::new(0));
+        let mut systems: Vec<_> = (0..2)
+            .map(|_| {
+                let (arrived, met) = (Arc::clone(&arrived), Arc::clone(&met));
+                probe(Some(vec![Access::read::<A>()]), move || {
+                    arrived.fetch_add(1, Ordering::SeqCst);
+                    let started = Instant::now();
+                    while arrived.load(Ordering::SeqCst) < 2 && started.elapsed() < Duration::from_secs(5) {
+                        thread::yield_now();
+                    }
+                    if arrived.load(Ordering::SeqCst) == 2 {
+                        met.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        run(&mut ParallelExecutor::new(2), &mut systems, &[vec![], vec![]]).unwrap();
+        assert_eq!(met.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn panic_of_a_system_is_raised_again() {
+        let other = Arc::new(AtomicBool::new(false));
+        let ran = Arc::clone(&other);
+        let mut executor = ParallelExecutor::new(2);
+        let mut systems = vec![
+            probe(Some(vec![]), || panic!("boom")),
+            probe(Some(vec![]), move || {
+                ran.store(true, Ordering::SeqCst);
+                Ok(())
+            }),
+        ];
+
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| run(&mut executor, &mut systems, &[vec![], vec![]])))
+            .unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+        assert!(other.load(Ordering::SeqCst));
+
+        // the pool is still usable
+        let mut systems = vec![probe(Some(vec![]), || Ok(()))];
+        assert!(run(&mut executor, &mut systems, &[vec![]]).is_ok());
+    }
+
+    #[test]
+    fn failed_system_stops_its_successors() {
+        let successor = Arc::new(AtomicUsize::new(0));
+        let independent = Arc::new(AtomicUsize::new(0));
+        let (s, i) = (Arc::clone(&successor), Arc::clone(&independent));
+        let mut systems = vec![
+            probe(Some(vec![]), || Err(EcsError::NoSuchResource { resource: "missing" })),
+            probe(Some(vec![]), move || {
+                s.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }),
+            probe(Some(vec![]), move || {
+                i.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }),
+        ];
+
+        let result = run(&mut ParallelExecutor::new(2), &mut systems, &[vec![], vec![0], vec![]]);
+        assert_eq!(result, Err(EcsError::NoSuchResource { resource: "missing" }));
+        assert_eq!(successor.load(Ordering::SeqCst), 0);
+        assert_eq!(independent.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn dependencies_run_in_order_on_one_thread() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut systems: Vec<_> = (0..4)
+            .map(|i| {
+                let log = Arc::clone(&log);
+                probe(Some(vec![]), move || {
+                    log.lock().unwrap().push(i);
+                    Ok(())
+                })
+            })
+            .collect();
+
+        run(&mut ParallelExecutor::new(1), &mut systems, &[vec![], vec![0], vec![0, 1], vec![2]]).unwrap();
+        assert_eq!(*log.lock().unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn threads_are_kept_between_runs() {
+        let threads = Arc::new(Mutex::new(HashSet::new()));
+        let mut executor = ParallelExecutor::new(3);
+        for _ in 0..20 {
+            let mut systems: Vec<_> = (0..4)
+                .map(|_| {
+                    let threads = Arc::clone(&threads);
+                    probe(Some(vec![]), move || {
+                        let current = thread::current();
+                        assert!(current.name().unwrap().starts_with("ecs-executor-"));
+                        threads.lock().unwrap().insert(current.id());
+                        Ok(())
+                    })
+                })
+                .collect();
+            run(&mut executor, &mut systems, &vec![vec![]; 4]).unwrap();
+        }
+
+        assert!(threads.lock().unwrap().len() <= 3);
+    }
+}
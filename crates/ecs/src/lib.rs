@@ -1,39 +1,151 @@
+mod cell;
+mod entity;
+mod component;
+mod archetype;
+mod sparse;
+mod query;
+mod resource;
+mod events;
+mod commands;
+mod system;
+mod executor;
+mod schedule;
 mod world;
 
+pub use cell::{ Ref, RefMut };
+pub use entity::Entity;
+pub use component::{ Component, ComponentId, ComponentInfo, Components, StorageType };
+pub use archetype::{ Archetype, ArchetypeId, Location };
+pub use sparse::SparseSet;
+pub use query::{ Access, Query, QueryFilter, With, Without, WorldQuery };
+pub use resource::{ Res, ResMut, Resource, Resources };
+pub use events::{ Event, EventReader, EventWriter, Events };
+pub use commands::{ CommandQueue, Commands };
+pub use system::{ FunctionSystem, IntoSystem, System, SystemParam };
+pub use executor::{ Executor, ParallelExecutor, SingleThreadedExecutor };
+pub use schedule::{ IntoSystemDescriptor, Schedule, StageId, SystemDescriptor };
+pub use world::{ World, EcsError };
 use world::{
-    World,
     Health,
-    Name
+    Name,
+    Perished,
+    Poisoned,
+    Turn
 };
 
 pub fn ecs_example() {
     println!("Hello, world!");
 
     let mut world = World::new();
+    world.insert_resource(Turn(0));
+    world.add_event::<Perished>();
+    // poison comes and goes, it is kept out of the tables
+    world.register_component::<Poisoned>(StorageType::SparseSet).unwrap();
 
     let entity0 = world.new_entity();
-    world.add_component_to_entity(entity0, Health(1000));
-    world.add_component_to_entity(entity0, Name("Pure Vessel"));
+    world.add_component_to_entity(entity0, Health(1000)).unwrap();
+    world.add_component_to_entity(entity0, Name("Pure Vessel")).unwrap();
 
     let entity2 = world.new_entity();
-    world.add_component_to_entity(entity2, Name("The Radiance"));
+    world.add_component_to_entity(entity2, Name("The Radiance")).unwrap();
 
     let entity1 = world.new_entity();
-    world.add_component_to_entity(entity1, Health(-10));
-    world.add_component_to_entity(entity1, Name("The Knight"));
+    world.add_component_to_entity(entity1, Health(-10)).unwrap();
+    world.add_component_to_entity(entity1, Name("The Knight")).unwrap();
+    world.add_component_to_entity(entity1, Poisoned(5)).unwrap();
 
-    let mut healths = world.borrow_component_vec_mut::<Health>().unwrap();
-    let mut names = world.borrow_component_vec_mut::<Name>().unwrap();
-    let zip = healths.iter_mut().zip(names.iter_mut());
-    let iter = zip.filter_map(|(health, name)| Some((health.as_mut()?, name.as_mut()?)));
+    // systems that do not touch the same components run at the same time
+    let mut schedule = Schedule::new();
+    schedule.set_executor(ParallelExecutor::default());
+    let update = schedule.add_stage("update");
+    let report = schedule.add_stage("report");
+    schedule
+        .add_system(update, revive.after(death_check))
+        .add_system(update, death_check.after(poison))
+        .add_system(update, poison)
+        .add_system(update, next_turn.before(poison))
+        .add_system(report, untouchable)
+        .add_system(report, chronicle.after(untouchable));
+    for _ in 0..2 {
+        schedule.run(&mut world).unwrap();
+    }
+
+    // a system borrowing `Health` twice fails instead of panicking
+    let mut broken = Schedule::new();
+    let stage = broken.add_stage("broken");
+    broken.add_system(stage, |_: Query<&Health>, _: Query<&mut Health>| {});
+    if let Err(err) = broken.run(&mut world) {
+        println!("{}", err);
+    }
+
+    let cured = world.remove_component_from_entity::<Poisoned>(entity1).unwrap();
+    println!("{} cured of {} poison", world.component::<Name>(entity1).unwrap().0, cured.map_or(0, |poison| poison.0));
+
+    // despawning while a query iterates has to wait until the query is done
+    let mut queue = CommandQueue::default();
+    let commands = Commands::new(&queue, &world);
+    for (entity, name) in &mut world.query_filtered::<(Entity, &Name), Without<Health>>().unwrap() {
+        println!("Banishing {}", name.0);
+        commands.despawn(entity);
+    }
+    queue.apply(&mut world).unwrap();
+
+    // the slot of a despawned entity is reused, its old handle stays dead
+    let entity3 = world.new_entity();
+    world.add_component_to_entity(entity3, Name("Hornet")).unwrap();
+    match world.add_component_to_entity(entity2, Health(1)) {
+        Ok(()) => println!("{} came back", entity2),
+        Err(err) => println!("{}", err),
+    }
+    println!("{} is now {}", entity3, world.component::<Name>(entity3).unwrap().0);
+}
+
+fn next_turn(mut turn: ResMut<Turn>) {
+    turn.0 += 1;
+    println!("Turn {}", turn.0);
+}
+
+// only the entities with both components are visited, poison is looked up in its sparse set
+fn poison(turn: Res<Turn>, mut query: Query<(&mut Health, &Name, &Poisoned)>) {
+    for (health, name, poisoned) in &mut query {
+        println!("{} takes {} poison damage", name.0, poisoned.0 * turn.0 as i32);
+        health.0 -= poisoned.0 * turn.0 as i32;
+    }
+}
 
-    for (health, name) in iter {
+// the shade cannot be spawned while the query borrows the world, it is recorded for the end of the stage
+fn death_check(mut query: Query<(Entity, &Health, &Name)>, mut perished: EventWriter<Perished>, commands: Commands) {
+    for (entity, health, name) in &mut query {
         if health.0 <= 0 {
             println!("{} has Perished", name.0);
-            println!("Reviving {} with 100 health", name.0);
-            *health = Health(100);
+            perished.send(Perished(entity));
+            let shade = commands.spawn();
+            commands.insert(shade, Name("Shade"));
         } else {
             println!("{} still Standing", name.0)
         }
     }
 }
+
+fn revive(mut perished: EventReader<Perished>, mut query: Query<(Entity, &mut Health, &Name)>) {
+    let perished: Vec<Entity> = perished.iter().map(|perished| perished.0).collect();
+    for (entity, health, name) in &mut query {
+        if perished.contains(&entity) {
+            println!("Reviving {} with 100 health", name.0);
+            *health = Health(100);
+        }
+    }
+}
+
+// every reader sees the events, whatever the other readers did with them
+fn chronicle(mut perished: EventReader<Perished>) {
+    for perished in perished.iter() {
+        println!("The chronicle records the fall of {}", perished.0);
+    }
+}
+
+fn untouchable(mut query: Query<(Entity, &Name), Without<Health>>) {
+    for (entity, name) in &mut query {
+        println!("{} ({}) cannot be hurt", name.0, entity);
+    }
+}
@@ -0,0 +1,548 @@
+use std::any::{ self, TypeId };
+use std::iter;
+use std::marker::PhantomData;
+
+use crate::archetype::Archetype;
+use crate::cell::{ BorrowError, Ref, RefMut };
+use crate::component::{ Component, ComponentId, StorageType };
+use crate::entity::Entity;
+use crate::resource::Resource;
+use crate::sparse::SparseSet;
+use crate::world::{ EcsError, World };
+
+// one component type or resource a query or system reads or writes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Access {
+    type_id: TypeId,
+    name: &'static str,
+    resource: bool,
+    mutable: bool
+}
+
+impl Access {
+    fn of<T: 'static>(resource: bool, mutable: bool) -> Self {
+        Access { type_id: TypeId::of::<T>(), name: any::type_name::<T>(), resource, mutable }
+    }
+
+    pub fn read<T: Component>() -> Self {
+        Access::of::<T>(false, false)
+    }
+
+    pub fn write<T: Component>() -> Self {
+        Access::of::<T>(false, true)
+    }
+
+    pub fn read_resource<R: Resource>() -> Self {
+        Access::of::<R>(true, false)
+    }
+
+    pub fn write_resource<R: Resource>() -> Self {
+        Access::of::<R>(true, true)
+    }
+
+    // the two cannot happen at the same time
+    pub fn conflicts(&self, other: &Access) -> bool {
+        self.type_id == other.type_id && self.resource == other.resource && (self.mutable || other.mutable)
+    }
+}
+
+// what a query fetches for every entity: `&T`, `&mut T`, `Option<&T>`, `Option<&mut T>`,
+// `Entity` or a tuple of those
+pub trait WorldQuery {
+    type Item<'a>;
+    // the storages it keeps borrowed while the query lives
+    type State<'w>;
+
+    fn access(access: &mut Vec<Access>);
+    // false when `archetype` lacks a table component it requires
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool;
+    fn borrow<'w>(world: &'w World, archetypes: &[&'w Archetype]) -> Result<Self::State<'w>, EcsError>;
+    // one item for every row of `archetypes`, none for the rows missing a required sparse set component
+    fn iter<'a, 'w>(
+        state: &'a mut Self::State<'w>,
+        archetypes: &'a [&'w Archetype]
+    ) -> impl Iterator<Item = Option<Self::Item<'a>>>;
+}
+
+// which entities a query visits, without fetching anything
+pub trait QueryFilter {
+    // the components it looks at, only read while the query is made
+    fn access(access: &mut Vec<Access>);
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool;
+    // clear the rows of `archetype` it rejects because of a sparse set component
+    fn filter_rows(world: &World, archetype: &Archetype, rows: &mut [bool]) -> Result<(), EcsError>;
+}
+
+// only the entities that have a `T`
+pub struct With<T>(PhantomData<T>);
+
+// only the entities that have no `T`
+pub struct Without<T>(PhantomData<T>);
+
+// the storages of one component type, borrowed for every matched archetype
+pub enum Borrowed<C, S> {
+    // a column per archetype, none for the archetypes without one
+    Table(Vec<Option<C>>),
+    Sparse(S)
+}
+
+type Shared<'w, T> = Borrowed<Ref<'w, Vec<T>>, Ref<'w, SparseSet<T>>>;
+type Exclusive<'w, T> = Borrowed<RefMut<'w, Vec<T>>, RefMut<'w, SparseSet<T>>>;
+
+// the entities of the matched archetypes with their items, borrowed from the world until dropped
+pub struct Query<'w, Q: WorldQuery, F: QueryFilter = ()> {
+    archetypes: Vec<&'w Archetype>,
+    state: Q::State<'w>,
+    // one per row of `archetypes`, false for the rows `F` rejects
+    rows: Vec<bool>,
+    filter: PhantomData<F>
+}
+
+impl<'w, Q: WorldQuery, F: QueryFilter> Query<'w, Q, F> {
+    pub(crate) fn new(world: &'w World) -> Result<Self, EcsError> {
+        let mut access = Vec::new();
+        Q::access(&mut access);
+        for (i, a) in access.iter().enumerate() {
+            if access[..i].iter().any(|b| a.conflicts(b)) {
+                return Err(EcsError::ConflictingAccess { component: a.name });
+            }
+        }
+
+        let archetypes: Vec<&Archetype> = world.archetypes_with(&[])
+            .filter(|archetype| !archetype.is_empty())
+            .filter(|archetype| Q::matches_archetype(world, archetype) && F::matches_archetype(world, archetype))
+            .collect();
+        let mut rows = Vec::new();
+        for archetype in archetypes.iter() {
+            let start = rows.len();
+            rows.resize(start + archetype.len(), true);
+            F::filter_rows(world, archetype, &mut rows[start..])?;
+        }
+
+        let state = Q::borrow(world, &archetypes)?;
+        Ok(Query { archetypes, state, rows, filter: PhantomData })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = Q::Item<'_>> + use<'_, 'w, Q, F> {
+        Q::iter(&mut self.state, &self.archetypes)
+            .zip(self.rows.iter())
+            .filter_map(|(item, &keep)| match keep {
+                true => item,
+                false => None,
+            })
+    }
+}
+
+impl<'a, 'w, Q: WorldQuery, F: QueryFilter> IntoIterator for &'a mut Query<'w, Q, F> {
+    type Item = Q::Item<'a>;
+    type IntoIter = Box<dyn Iterator<Item = Q::Item<'a>> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter_mut())
+    }
+}
+
+fn storage<T: Component>(world: &World) -> Option<(ComponentId, StorageType)> {
+    let id = world.components().id::<T>()?;
+    Some((id, world.components().info(id).storage))
+}
+
+// false when `T` is a table component `archetype` does not have
+fn has_table_component<T: Component>(world: &World, archetype: &Archetype) -> bool {
+    match storage::<T>(world) {
+        Some((id, StorageType::Table)) => archetype.contains(id),
+        Some((_, StorageType::SparseSet)) => true,
+        None => false,
+    }
+}
+
+fn borrow_error<T>(_: BorrowError) -> EcsError {
+    EcsError::AlreadyBorrowed { component: any::type_name::<T>() }
+}
+
+fn borrow<'w, T: Component>(
+    world: &'w World,
+    archetypes: &[&'w Archetype]
+) -> Result<Shared<'w, T>, EcsError> {
+    match storage::<T>(world) {
+        Some((_, StorageType::SparseSet)) => {
+            let sparse_set = world.try_sparse_set::<T>().map_err(borrow_error::<T>)?;
+            Ok(Borrowed::Sparse(sparse_set.unwrap()))
+        },
+        Some((id, StorageType::Table)) => archetypes.iter()
+            .map(|archetype| archetype.try_column::<T>(id).map_err(borrow_error::<T>))
+            .collect::<Result<_, _>>()
+            .map(Borrowed::Table),
+        None => Ok(Borrowed::Table(archetypes.iter().map(|_| None).collect())),
+    }
+}
+
+fn borrow_mut<'w, T: Component>(
+    world: &'w World,
+    archetypes: &[&'w Archetype]
+) -> Result<Exclusive<'w, T>, EcsError> {
+    match storage::<T>(world) {
+        Some((_, StorageType::SparseSet)) => {
+            let sparse_set = world.try_sparse_set_mut::<T>().map_err(borrow_error::<T>)?;
+            Ok(Borrowed::Sparse(sparse_set.unwrap()))
+        },
+        Some((id, StorageType::Table)) => archetypes.iter()
+            .map(|archetype| archetype.try_column_mut::<T>(id).map_err(borrow_error::<T>))
+            .collect::<Result<_, _>>()
+            .map(Borrowed::Table),
+        None => Ok(Borrowed::Table(archetypes.iter().map(|_| None).collect())),
+    }
+}
+
+// the value of every row, none where the entity has no `T`
+fn rows<'a, 'w, T: Component>(
+    borrowed: &'a Shared<'w, T>,
+    archetypes: &'a [&'w Archetype]
+) -> Box<dyn Iterator<Item = Option<&'a T>> + 'a> {
+    match borrowed {
+        Borrowed::Table(columns) => Box::new(columns.iter().zip(archetypes).flat_map(|(column, archetype)| {
+            let missing = if column.is_some() { 0 } else { archetype.len() };
+            column.iter().flat_map(|column| column.iter()).map(Some).chain(iter::repeat_with(|| None).take(missing))
+        })),
+        Borrowed::Sparse(sparse_set) => Box::new(
+            archetypes.iter().flat_map(|archetype| archetype.entities()).map(move |entity| sparse_set.get(*entity))
+        ),
+    }
+}
+
+fn rows_mut<'a, 'w, T: Component>(
+    borrowed: &'a mut Exclusive<'w, T>,
+    archetypes: &'a [&'w Archetype]
+) -> Box<dyn Iterator<Item = Option<&'a mut T>> + 'a> {
+    match borrowed {
+        Borrowed::Table(columns) => Box::new(columns.iter_mut().zip(archetypes).flat_map(|(column, archetype)| {
+            let missing = if column.is_some() { 0 } else { archetype.len() };
+            column.iter_mut().flat_map(|column| column.iter_mut()).map(Some).chain(iter::repeat_with(|| None).take(missing))
+        })),
+        Borrowed::Sparse(sparse_set) => {
+            let mut take = sparse_set.take_values_mut();
+            Box::new(archetypes.iter().flat_map(|archetype| archetype.entities()).map(move |entity| take(*entity)))
+        },
+    }
+}
+
+impl<T: Component> WorldQuery for &T {
+    type Item<'a> = &'a T;
+    type State<'w> = Shared<'w, T>;
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::read::<T>());
+    }
+
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+        has_table_component::<T>(world, archetype)
+    }
+
+    fn borrow<'w>(world: &'w World, archetypes: &[&'w Archetype]) -> Result<Self::State<'w>, EcsError> {
+        borrow(world, archetypes)
+    }
+
+    fn iter<'a, 'w>(
+        state: &'a mut Self::State<'w>,
+        archetypes: &'a [&'w Archetype]
+    ) -> impl Iterator<Item = Option<Self::Item<'a>>> {
+        rows(state, archetypes)
+    }
+}
+
+impl<T: Component> WorldQuery for &mut T {
+    type Item<'a> = &'a mut T;
+    type State<'w> = Exclusive<'w, T>;
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::write::<T>());
+    }
+
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+        has_table_component::<T>(world, archetype)
+    }
+
+    fn borrow<'w>(world: &'w World, archetypes: &[&'w Archetype]) -> Result<Self::State<'w>, EcsError> {
+        borrow_mut(world, archetypes)
+    }
+
+    fn iter<'a, 'w>(
+        state: &'a mut Self::State<'w>,
+        archetypes: &'a [&'w Archetype]
+    ) -> impl Iterator<Item = Option<Self::Item<'a>>> {
+        rows_mut(state, archetypes)
+    }
+}
+
+impl<T: Component> WorldQuery for Option<&T> {
+    type Item<'a> = Option<&'a T>;
+    type State<'w> = Shared<'w, T>;
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::read::<T>());
+    }
+
+    fn matches_archetype(_: &World, _: &Archetype) -> bool {
+        true
+    }
+
+    fn borrow<'w>(world: &'w World, archetypes: &[&'w Archetype]) -> Result<Self::State<'w>, EcsError> {
+        borrow(world, archetypes)
+    }
+
+    fn iter<'a, 'w>(
+        state: &'a mut Self::State<'w>,
+        archetypes: &'a [&'w Archetype]
+    ) -> impl Iterator<Item = Option<Self::Item<'a>>> {
+        rows(state, archetypes).map(Some)
+    }
+}
+
+impl<T: Component> WorldQuery for Option<&mut T> {
+    type Item<'a> = Option<&'a mut T>;
+    type State<'w> = Exclusive<'w, T>;
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::write::<T>());
+    }
+
+    fn matches_archetype(_: &World, _: &Archetype) -> bool {
+        true
+    }
+
+    fn borrow<'w>(world: &'w World, archetypes: &[&'w Archetype]) -> Result<Self::State<'w>, EcsError> {
+        borrow_mut(world, archetypes)
+    }
+
+    fn iter<'a, 'w>(
+        state: &'a mut Self::State<'w>,
+        archetypes: &'a [&'w Archetype]
+    ) -> impl Iterator<Item = Option<Self::Item<'a>>> {
+        rows_mut(state, archetypes).map(Some)
+    }
+}
+
+impl WorldQuery for Entity {
+    type Item<'a> = Entity;
+    type State<'w> = ();
+
+    fn access(_: &mut Vec<Access>) {}
+
+    fn matches_archetype(_: &World, _: &Archetype) -> bool {
+        true
+    }
+
+    fn borrow<'w>(_: &'w World, _: &[&'w Archetype]) -> Result<Self::State<'w>, EcsError> {
+        Ok(())
+    }
+
+    fn iter<'a, 'w>(
+        _: &'a mut Self::State<'w>,
+        archetypes: &'a [&'w Archetype]
+    ) -> impl Iterator<Item = Option<Self::Item<'a>>> {
+        archetypes.iter().flat_map(|archetype| archetype.entities()).map(|entity| Some(*entity))
+    }
+}
+
+// clear the rows whose entity has a `T` in its sparse set, or lacks one when `keep`
+fn sparse_rows<T: Component>(
+    world: &World,
+    archetype: &Archetype,
+    rows: &mut [bool],
+    keep: bool
+) -> Result<(), EcsError> {
+    if let Some((_, StorageType::SparseSet)) = storage::<T>(world) {
+        let sparse_set = world.try_sparse_set::<T>().map_err(borrow_error::<T>)?.unwrap();
+        for (row, entity) in rows.iter_mut().zip(archetype.entities()) {
+            *row &= sparse_set.contains(*entity) == keep;
+        }
+    }
+    Ok(())
+}
+
+impl<T: Component> QueryFilter for With<T> {
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::read::<T>());
+    }
+
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+        has_table_component::<T>(world, archetype)
+    }
+
+    fn filter_rows(world: &World, archetype: &Archetype, rows: &mut [bool]) -> Result<(), EcsError> {
+        sparse_rows::<T>(world, archetype, rows, true)
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::read::<T>());
+    }
+
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+        match storage::<T>(world) {
+            Some((id, StorageType::Table)) => !archetype.contains(id),
+            _ => true,
+        }
+    }
+
+    fn filter_rows(world: &World, archetype: &Archetype, rows: &mut [bool]) -> Result<(), EcsError> {
+        sparse_rows::<T>(world, archetype, rows, false)
+    }
+}
+
+macro_rules! tuple_query {
+    ($($name:ident $item:ident),*) => {
+        impl<$($name: WorldQuery),*> WorldQuery for ($($name,)*) {
+            type Item<'a> = ($($name::Item<'a>,)*);
+            type State<'w> = ($($name::State<'w>,)*);
+
+            fn access(access: &mut Vec<Access>) {
+                $($name::access(access);)*
+            }
+
+            fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+                true $(&& $name::matches_archetype(world, archetype))*
+            }
+
+            fn borrow<'w>(world: &'w World, archetypes: &[&'w Archetype]) -> Result<Self::State<'w>, EcsError> {
+                Ok(($($name::borrow(world, archetypes)?,)*))
+            }
+
+            fn iter<'a, 'w>(
+                state: &'a mut Self::State<'w>,
+                archetypes: &'a [&'w Archetype]
+            ) -> impl Iterator<Item = Option<Self::Item<'a>>> {
+                let ($($item,)*) = state;
+                $(let mut $item = $name::iter($item, archetypes);)*
+                iter::from_fn(move || Some(match ($($item.next()?,)*) {
+                    ($(Some($item),)*) => Some(($($item,)*)),
+                    _ => None,
+                }))
+            }
+        }
+
+        impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            fn access(access: &mut Vec<Access>) {
+                $($name::access(access);)*
+            }
+
+            fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+                true $(&& $name::matches_archetype(world, archetype))*
+            }
+
+            fn filter_rows(world: &World, archetype: &Archetype, rows: &mut [bool]) -> Result<(), EcsError> {
+                $($name::filter_rows(world, archetype, rows)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+tuple_query!(A a);
+tuple_query!(A a, B b);
+tuple_query!(A a, B b, C c);
+tuple_query!(A a, B b, C c, D d);
+tuple_query!(A a, B b, C c, D d, E e);
+tuple_query!(A a, B b, C c, D d, E e, F f);
+
+impl QueryFilter for () {
+    fn access(_: &mut Vec<Access>) {}
+
+    fn matches_archetype(_: &World, _: &Archetype) -> bool {
+        true
+    }
+
+    fn filter_rows(_: &World, _: &Archetype, _: &mut [bool]) -> Result<(), EcsError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Position(i32);
+    struct Velocity;
+    struct Frozen;
+    // added and removed all the time
+    struct Burning(i32);
+
+    fn world() -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        world.register_component::<Burning>(StorageType::SparseSet).unwrap();
+        let entities: Vec<Entity> = (0..4).map(|_| world.new_entity()).collect();
+        for (i, &entity) in entities.iter().enumerate() {
+            world.add_component_to_entity(entity, Position(i as i32)).unwrap();
+        }
+        world.add_component_to_entity(entities[1], Velocity).unwrap();
+        world.add_component_to_entity(entities[2], Velocity).unwrap();
+        world.add_component_to_entity(entities[2], Frozen).unwrap();
+        world.add_component_to_entity(entities[0], Burning(1)).unwrap();
+        world.add_component_to_entity(entities[2], Burning(3)).unwrap();
+        (world, entities)
+    }
+
+    fn sorted<T: Ord>(mut items: Vec<T>) -> Vec<T> {
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn filters_pick_the_entities_with_or_without_a_component() {
+        let (world, entities) = world();
+        let moving: Vec<usize> = world.query_filtered::<Entity, With<Velocity>>().unwrap()
+            .iter_mut().map(|entity| entity.index()).collect();
+        assert_eq!(sorted(moving), [entities[1].index(), entities[2].index()]);
+
+        let thawed: Vec<i32> = world.query_filtered::<&Position, (With<Velocity>, Without<Frozen>)>().unwrap()
+            .iter_mut().map(|position| position.0).collect();
+        assert_eq!(thawed, [1]);
+
+        // filters on a sparse set component work row by row
+        let cold: Vec<i32> = world.query_filtered::<&Position, Without<Burning>>().unwrap()
+            .iter_mut().map(|position| position.0).collect();
+        assert_eq!(sorted(cold), [1, 3]);
+    }
+
+    #[test]
+    fn table_and_sparse_components_mix_in_one_query() {
+        let (world, _) = world();
+        let burning: Vec<(i32, i32)> = world.query::<(&Position, &Burning)>().unwrap()
+            .iter_mut().map(|(position, burning)| (position.0, burning.0)).collect();
+        assert_eq!(sorted(burning), [(0, 1), (2, 3)]);
+
+        for (position, mut burning) in world.query::<(&Position, Option<&mut Burning>)>().unwrap().iter_mut() {
+            if let Some(burning) = burning.as_mut() {
+                burning.0 += position.0 * 10;
+            }
+        }
+        let burning: Vec<i32> = world.query::<&Burning>().unwrap().iter_mut().map(|burning| burning.0).collect();
+        assert_eq!(sorted(burning), [1, 23]);
+    }
+
+    #[test]
+    fn optional_sparse_component_is_none_where_it_is_missing() {
+        let (world, _) = world();
+        let mut seen: Vec<(i32, Option<i32>)> = world.query::<(&Position, Option<&mut Burning>)>().unwrap()
+            .iter_mut().map(|(position, burning)| (position.0, burning.map(|burning| burning.0))).collect();
+        seen.sort();
+        assert_eq!(seen, [(0, Some(1)), (1, None), (2, Some(3)), (3, None)]);
+    }
+
+    #[test]
+    fn conflicting_or_borrowed_storages_are_errors() {
+        let (world, _) = world();
+        assert!(matches!(world.query::<(&mut Position, &Position)>(), Err(EcsError::ConflictingAccess { .. })));
+        assert!(matches!(world.query::<(&mut Burning, Option<&Burning>)>(), Err(EcsError::ConflictingAccess { .. })));
+
+        let writing = world.query::<&mut Burning>().unwrap();
+        assert!(matches!(world.query::<&Burning>(), Err(EcsError::AlreadyBorrowed { .. })));
+        drop(writing);
+        let reading = world.query::<&Position>().unwrap();
+        assert!(world.query::<&Position>().is_ok());
+        assert!(matches!(world.query::<&mut Position>(), Err(EcsError::AlreadyBorrowed { .. })));
+        drop(reading);
+        assert!(world.query::<&mut Position>().is_ok());
+    }
+}
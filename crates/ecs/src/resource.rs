@@ -0,0 +1,89 @@
+use std::any::{ Any, TypeId };
+use std::collections::HashMap;
+use std::ops::{ Deref, DerefMut };
+
+use crate::cell::{ AtomicRefCell, BorrowError, Ref, RefMut };
+
+// data of the world that belongs to no entity, one value per type
+pub trait Resource: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Resource for T {}
+
+#[derive(Default)]
+pub struct Resources {
+    resources: HashMap<TypeId, AtomicRefCell<Box<dyn Any + Send + Sync>>>
+}
+
+impl Resources {
+    // returns the value it replaces
+    pub fn insert<R: Resource>(&mut self, resource: R) -> Option<R> {
+        let previous = self.resources.insert(TypeId::of::<R>(), AtomicRefCell::new(Box::new(resource)))?;
+        Some(*previous.into_inner().downcast::<R>().unwrap())
+    }
+
+    pub fn remove<R: Resource>(&mut self) -> Option<R> {
+        let resource = self.resources.remove(&TypeId::of::<R>())?;
+        Some(*resource.into_inner().downcast::<R>().unwrap())
+    }
+
+    pub fn contains<R: Resource>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+
+    // none when there is no `R`, an error when it is borrowed mutably elsewhere
+    pub fn try_get<R: Resource>(&self) -> Result<Option<Ref<'_, R>>, BorrowError> {
+        let resource = match self.resources.get(&TypeId::of::<R>()) {
+            Some(resource) => resource.try_borrow()?,
+            None => return Ok(None),
+        };
+        Ok(Ref::filter_map(resource, |resource| resource.downcast_ref::<R>()))
+    }
+
+    pub fn try_get_mut<R: Resource>(&self) -> Result<Option<RefMut<'_, R>>, BorrowError> {
+        let resource = match self.resources.get(&TypeId::of::<R>()) {
+            Some(resource) => resource.try_borrow_mut()?,
+            None => return Ok(None),
+        };
+        Ok(RefMut::filter_map(resource, |resource| resource.downcast_mut::<R>()))
+    }
+}
+
+// system parameter reading the resource `R`
+pub struct Res<'w, R: Resource>(Ref<'w, R>);
+
+impl<'w, R: Resource> Res<'w, R> {
+    pub(crate) fn new(resource: Ref<'w, R>) -> Self {
+        Res(resource)
+    }
+}
+
+impl<R: Resource> Deref for Res<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.0
+    }
+}
+
+// system parameter writing the resource `R`
+pub struct ResMut<'w, R: Resource>(RefMut<'w, R>);
+
+impl<'w, R: Resource> ResMut<'w, R> {
+    pub(crate) fn new(resource: RefMut<'w, R>) -> Self {
+        ResMut(resource)
+    }
+}
+
+impl<R: Resource> Deref for ResMut<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.0
+    }
+}
+
+impl<R: Resource> DerefMut for ResMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut R {
+        &mut self.0
+    }
+}
@@ -0,0 +1,253 @@
+use crate::executor::{ Executor, SingleThreadedExecutor };
+use crate::system::{ IntoSystem, System };
+use crate::world::{ EcsError, World };
+
+// a system with the systems of its stage it has to run before and after
+pub struct SystemDescriptor {
+    system: Box<dyn System>,
+    before: Vec<&'static str>,
+    after: Vec<&'static str>
+}
+
+pub trait IntoSystemDescriptor<Params> {
+    fn into_descriptor(self) -> SystemDescriptor;
+
+    fn before<P>(self, system: impl IntoSystem<P>) -> SystemDescriptor
+    where
+        Self: Sized
+    {
+        let mut descriptor = self.into_descriptor();
+        descriptor.before.push(system.into_system().name());
+        descriptor
+    }
+
+    fn after<P>(self, system: impl IntoSystem<P>) -> SystemDescriptor
+    where
+        Self: Sized
+    {
+        let mut descriptor = self.into_descriptor();
+        descriptor.after.push(system.into_system().name());
+        descriptor
+    }
+}
+
+impl<P, S: IntoSystem<P>> IntoSystemDescriptor<P> for S {
+    fn into_descriptor(self) -> SystemDescriptor {
+        SystemDescriptor { system: Box::new(self.into_system()), before: Vec::new(), after: Vec::new() }
+    }
+}
+
+impl IntoSystemDescriptor<SystemDescriptor> for SystemDescriptor {
+    fn into_descriptor(self) -> SystemDescriptor {
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageId(usize);
+
+struct Stage {
+    name: &'static str,
+    systems: Vec<Box<dyn System>>,
+    // the names given to `before` and `after` of every system
+    before: Vec<Vec<&'static str>>,
+    after: Vec<Vec<&'static str>>,
+    // `sort` of the systems, done again after a system is added
+    plan: Option<(Vec<usize>, Vec<Vec<usize>>)>
+}
+
+impl Stage {
+    // the systems in insertion order, except where `before` and `after` say otherwise,
+    // with the systems each one has to run after
+    fn sort(&self) -> Result<(Vec<usize>, Vec<Vec<usize>>), EcsError> {
+        let n = self.systems.len();
+        let mut predecessors = vec![Vec::new(); n];
+
+        // closures of the same function share their name, none of them can be told apart
+        let find = |i: usize, dependency: &'static str| {
+            let found: Vec<usize> = (0..n).filter(|&j| self.systems[j].name() == dependency).collect();
+            match found.len() {
+                0 => Err(EcsError::UnknownDependency { system: self.systems[i].name(), dependency }),
+                1 => Ok(found[0]),
+                _ => Err(EcsError::AmbiguousDependency { system: self.systems[i].name(), dependency }),
+            }
+        };
+        for i in 0..n {
+            for &dependency in self.before[i].iter() {
+                predecessors[find(i, dependency)?].push(i);
+            }
+            for &dependency in self.after[i].iter() {
+                predecessors[i].push(find(i, dependency)?);
+            }
+        }
+
+        let mut order = Vec::with_capacity(n);
+        let mut done = vec![false; n];
+        while order.len() < n {
+            let next = (0..n).find(|&i| !done[i] && predecessors[i].iter().all(|&j| done[j]));
+            let next = match next {
+                Some(next) => next,
+                None => {
+                    let stuck = (0..n).find(|&i| !done[i]).unwrap();
+                    return Err(EcsError::DependencyCycle { system: self.systems[stuck].name(), stage: self.name });
+                },
+            };
+            done[next] = true;
+            order.push(next);
+        }
+        Ok((order, predecessors))
+    }
+}
+
+// the stages run one after the other every tick, in the order they were added,
+// the executor decides how the systems of a stage run
+pub struct Schedule {
+    stages: Vec<Stage>,
+    executor: Box<dyn Executor>
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Schedule { stages: Vec::new(), executor: Box::new(SingleThreadedExecutor) }
+    }
+
+    pub fn set_executor(&mut self, executor: impl Executor + 'static) -> &mut Self {
+        self.executor = Box::new(executor);
+        self
+    }
+
+    pub fn add_stage(&mut self, name: &'static str) -> StageId {
+        self.stages.push(Stage { name, systems: Vec::new(), before: Vec::new(), after: Vec::new(), plan: None });
+        StageId(self.stages.len() - 1)
+    }
+
+    pub fn add_system<P>(&mut self, stage: StageId, system: impl IntoSystemDescriptor<P>) -> &mut Self {
+        let SystemDescriptor { system, before, after } = system.into_descriptor();
+        let stage = &mut self.stages[stage.0];
+        stage.systems.push(system);
+        stage.before.push(before);
+        stage.after.push(after);
+        stage.plan = None;
+        self
+    }
+
+    // one tick, the stages after the one where a system failed do not run,
+    // the events are updated all the same and the error is returned after
+    pub fn run(&mut self, world: &mut World) -> Result<(), EcsError> {
+        let result = self.run_stages(world);
+        world.update_events();
+        result
+    }
+
+    fn run_stages(&mut self, world: &mut World) -> Result<(), EcsError> {
+        for stage in self.stages.iter_mut() {
+            if stage.plan.is_none() {
+                stage.plan = Some(stage.sort()?);
+            }
+            let (order, after) = stage.plan.as_ref().unwrap();
+            self.executor.run(&mut stage.systems, order, after, world)?;
+            // the sync point, where the commands of the stage change the world
+            for &system in order.iter() {
+                stage.systems[system].apply(world)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Events;
+    use crate::resource::ResMut;
+
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    fn a(mut log: ResMut<Log>) {
+        log.0.push("a");
+    }
+
+    fn b(mut log: ResMut<Log>) {
+        log.0.push("b");
+    }
+
+    fn c(mut log: ResMut<Log>) {
+        log.0.push("c");
+    }
+
+    fn run(schedule: &mut Schedule) -> Result<Vec<&'static str>, EcsError> {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        schedule.run(&mut world)?;
+        let log = world.remove_resource::<Log>().unwrap();
+        Ok(log.0)
+    }
+
+    #[test]
+    fn systems_follow_before_and_after_then_insertion_order() {
+        let mut schedule = Schedule::new();
+        let first = schedule.add_stage("first");
+        let second = schedule.add_stage("second");
+        schedule
+            .add_system(second, a)
+            .add_system(first, c.after(b).before(a))
+            .add_system(first, a)
+            .add_system(first, b);
+
+        assert_eq!(run(&mut schedule).unwrap(), ["b", "c", "a", "a"]);
+    }
+
+    #[test]
+    fn cycles_and_unknown_systems_are_errors() {
+        let mut schedule = Schedule::new();
+        let stage = schedule.add_stage("update");
+        schedule.add_system(stage, a.after(b)).add_system(stage, b.after(a));
+        assert!(matches!(run(&mut schedule), Err(EcsError::DependencyCycle { stage: "update", .. })));
+
+        let mut schedule = Schedule::new();
+        let stage = schedule.add_stage("update");
+        schedule.add_system(stage, a.after(b));
+        assert!(matches!(run(&mut schedule), Err(EcsError::UnknownDependency { .. })));
+    }
+
+    #[test]
+    fn closures_of_one_function_are_ambiguous_dependencies() {
+        let first = |mut log: ResMut<Log>| log.0.push("first");
+        let second = |mut log: ResMut<Log>| log.0.push("second");
+        assert_eq!(first.into_system().name(), second.into_system().name());
+
+        let mut schedule = Schedule::new();
+        let stage = schedule.add_stage("update");
+        schedule.add_system(stage, first).add_system(stage, second.after(first)).add_system(stage, a);
+        assert!(matches!(run(&mut schedule), Err(EcsError::AmbiguousDependency { .. })));
+
+        // a closure can still order itself against a named function
+        let mut schedule = Schedule::new();
+        let stage = schedule.add_stage("update");
+        schedule.add_system(stage, first.after(a)).add_system(stage, a);
+        assert_eq!(run(&mut schedule).unwrap(), ["a", "first"]);
+    }
+
+    #[test]
+    fn failed_tick_still_updates_the_events() {
+        let mut schedule = Schedule::new();
+        let stage = schedule.add_stage("stage");
+        schedule.add_system(stage, a);
+        let mut world = World::new();
+        world.add_event::<&'static str>();
+        world.resource_mut::<Events<&'static str>>().unwrap().send("sent");
+
+        // there is no `Log` resource for `a`
+        for _ in 0..2 {
+            assert!(matches!(schedule.run(&mut world), Err(EcsError::NoSuchResource { .. })));
+        }
+        assert!(world.resource::<Events<&'static str>>().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use std::any::Any;
+
+use crate::component::Component;
+use crate::entity::Entity;
+
+// components kept apart from the archetype tables, adding and removing them
+// never moves the entity, values stay packed for iteration
+pub struct SparseSet<T> {
+    // position in `dense` of every entity slot that has a value
+    sparse: Vec<Option<usize>>,
+    dense: Vec<T>,
+    // owner of every value in `dense`
+    entities: Vec<Entity>
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        SparseSet { sparse: Vec::new(), dense: Vec::new(), entities: Vec::new() }
+    }
+}
+
+impl<T> SparseSet<T> {
+    // returns the value `entity` had before
+    pub fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        if let Some(dense) = self.dense_index(entity) {
+            return Some(std::mem::replace(&mut self.dense[dense], value));
+        }
+
+        if self.sparse.len() <= entity.index() {
+            self.sparse.resize(entity.index() + 1, None);
+        }
+        self.sparse[entity.index()] = Some(self.dense.len());
+        self.dense.push(value);
+        self.entities.push(entity);
+        None
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let dense = self.dense_index(entity)?;
+        self.sparse[entity.index()] = None;
+        self.entities.swap_remove(dense);
+        if let Some(moved) = self.entities.get(dense) {
+            self.sparse[moved.index()] = Some(dense);
+        }
+
+        Some(self.dense.swap_remove(dense))
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        Some(&self.dense[self.dense_index(entity)?])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let dense = self.dense_index(entity)?;
+        Some(&mut self.dense[dense])
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    // the entities with a value, in the same order as `values`
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.dense
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.dense
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().cloned().zip(self.dense.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.entities.iter().cloned().zip(self.dense.iter_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    // hands out the value of every entity at most once,
+    // so rows that each belong to a different entity can all be borrowed mutably
+    pub(crate) fn take_values_mut<'s>(&'s mut self) -> impl FnMut(Entity) -> Option<&'s mut T> + 's {
+        let sparse = &self.sparse;
+        let entities = &self.entities;
+        let mut values: Vec<Option<&mut T>> = self.dense.iter_mut().map(Some).collect();
+
+        move |entity| {
+            let dense = (*sparse.get(entity.index())?)?;
+            match entities[dense] == entity {
+                true => values[dense].take(),
+                false => None,
+            }
+        }
+    }
+
+    // a stale handle does not see the value of the entity now in its slot
+    pub(crate) fn dense_index(&self, entity: Entity) -> Option<usize> {
+        let dense = (*self.sparse.get(entity.index())?)?;
+        match self.entities[dense] == entity {
+            true => Some(dense),
+            false => None,
+        }
+    }
+}
+
+// a sparse set of any component type
+pub(crate) trait SparseStorage: Any + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn remove_entity(&mut self, entity: Entity);
+}
+
+impl<T: Component> SparseStorage for SparseSet<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+}
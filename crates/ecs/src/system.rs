@@ -0,0 +1,239 @@
+use std::any;
+
+use crate::commands::{ CommandQueue, Commands };
+use crate::events::{ Event, EventReader, EventWriter, Events };
+use crate::query::{ Access, Query, QueryFilter, WorldQuery };
+use crate::resource::{ Res, ResMut, Resource };
+use crate::world::{ EcsError, World };
+
+// logic run on the world every tick, maybe on another thread
+pub trait System: Send {
+    // what `before` and `after` refer to
+    fn name(&self) -> &'static str;
+    // the components it reads and writes, none when it may touch any of them
+    fn access(&self) -> Option<Vec<Access>> {
+        None
+    }
+    fn run(&mut self, world: &World) -> Result<(), EcsError>;
+    // the changes it deferred with `Commands`, at the end of its stage
+    fn apply(&mut self, _: &mut World) -> Result<(), EcsError> {
+        Ok(())
+    }
+}
+
+// what a function system takes as argument, borrowed from the world before each run
+pub trait SystemParam {
+    type Item<'w>;
+    // kept by the system from one run to the next
+    type State: Default + Send + 'static;
+
+    fn access(access: &mut Vec<Access>);
+    fn fetch<'w>(state: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError>;
+    fn apply(_: &mut Self::State, _: &mut World) -> Result<(), EcsError> {
+        Ok(())
+    }
+}
+
+impl<Q: WorldQuery, F: QueryFilter> SystemParam for Query<'_, Q, F> {
+    type Item<'w> = Query<'w, Q, F>;
+    type State = ();
+
+    fn access(access: &mut Vec<Access>) {
+        Q::access(access);
+        F::access(access);
+    }
+
+    fn fetch<'w>(_: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError> {
+        Query::new(world)
+    }
+}
+
+impl<R: Resource> SystemParam for Res<'_, R> {
+    type Item<'w> = Res<'w, R>;
+    type State = ();
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::read_resource::<R>());
+    }
+
+    fn fetch<'w>(_: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError> {
+        let resource = any::type_name::<R>();
+        match world.resources().try_get::<R>() {
+            Ok(Some(value)) => Ok(Res::new(value)),
+            Ok(None) => Err(EcsError::NoSuchResource { resource }),
+            Err(_) => Err(EcsError::ResourceAlreadyBorrowed { resource }),
+        }
+    }
+}
+
+impl<R: Resource> SystemParam for ResMut<'_, R> {
+    type Item<'w> = ResMut<'w, R>;
+    type State = ();
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::write_resource::<R>());
+    }
+
+    fn fetch<'w>(_: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError> {
+        let resource = any::type_name::<R>();
+        match world.resources().try_get_mut::<R>() {
+            Ok(Some(value)) => Ok(ResMut::new(value)),
+            Ok(None) => Err(EcsError::NoSuchResource { resource }),
+            Err(_) => Err(EcsError::ResourceAlreadyBorrowed { resource }),
+        }
+    }
+}
+
+impl<E: Event> SystemParam for EventWriter<'_, E> {
+    type Item<'w> = EventWriter<'w, E>;
+    type State = ();
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::write_resource::<Events<E>>());
+    }
+
+    fn fetch<'w>(_: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError> {
+        let resource = any::type_name::<Events<E>>();
+        match world.resources().try_get_mut::<Events<E>>() {
+            Ok(Some(events)) => Ok(EventWriter::new(events)),
+            Ok(None) => Err(EcsError::NoSuchResource { resource }),
+            Err(_) => Err(EcsError::ResourceAlreadyBorrowed { resource }),
+        }
+    }
+}
+
+impl<E: Event> SystemParam for EventReader<'_, E> {
+    type Item<'w> = EventReader<'w, E>;
+    // the id of the next event to read
+    type State = usize;
+
+    fn access(access: &mut Vec<Access>) {
+        access.push(Access::read_resource::<Events<E>>());
+    }
+
+    fn fetch<'w>(state: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError> {
+        let resource = any::type_name::<Events<E>>();
+        match world.resources().try_get::<Events<E>>() {
+            Ok(Some(events)) => Ok(EventReader::new(state, events)),
+            Ok(None) => Err(EcsError::NoSuchResource { resource }),
+            Err(_) => Err(EcsError::ResourceAlreadyBorrowed { resource }),
+        }
+    }
+}
+
+impl SystemParam for Commands<'_> {
+    type Item<'w> = Commands<'w>;
+    type State = CommandQueue;
+
+    // the world is only changed at the sync point
+    fn access(_: &mut Vec<Access>) {}
+
+    fn fetch<'w>(state: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError> {
+        Ok(Commands::new(state, world))
+    }
+
+    fn apply(state: &mut Self::State, world: &mut World) -> Result<(), EcsError> {
+        state.apply(world)
+    }
+}
+
+// a system, or a function whose every argument is a `SystemParam`
+pub trait IntoSystem<Params> {
+    type System: System + 'static;
+
+    fn into_system(self) -> Self::System;
+}
+
+// `Params` of the systems that are already one
+pub struct IsSystem;
+
+impl<S: System + 'static> IntoSystem<IsSystem> for S {
+    type System = S;
+
+    fn into_system(self) -> Self::System {
+        self
+    }
+}
+
+pub struct FunctionSystem<Func, Params: SystemParam> {
+    func: Func,
+    name: &'static str,
+    access: Vec<Access>,
+    state: Params::State
+}
+
+macro_rules! function_system {
+    ($($param:ident $value:ident),*) => {
+        impl<$($param: SystemParam),*> SystemParam for ($($param,)*) {
+            type Item<'w> = ($($param::Item<'w>,)*);
+            type State = ($($param::State,)*);
+
+            #[allow(unused_variables)]
+            fn access(access: &mut Vec<Access>) {
+                $($param::access(access);)*
+            }
+
+            #[allow(unused_variables)]
+            fn fetch<'w>(state: &'w mut Self::State, world: &'w World) -> Result<Self::Item<'w>, EcsError> {
+                let ($($value,)*) = state;
+                Ok(($($param::fetch($value, world)?,)*))
+            }
+
+            #[allow(unused_variables)]
+            fn apply(state: &mut Self::State, world: &mut World) -> Result<(), EcsError> {
+                let ($($value,)*) = state;
+                $($param::apply($value, world)?;)*
+                Ok(())
+            }
+        }
+
+        impl<Func, $($param: SystemParam + 'static),*> System for FunctionSystem<Func, ($($param,)*)>
+        where
+            Func: FnMut($($param),*) + for<'w> FnMut($($param::Item<'w>),*) + Send
+        {
+            fn name(&self) -> &'static str {
+                self.name
+            }
+
+            fn access(&self) -> Option<Vec<Access>> {
+                Some(self.access.clone())
+            }
+
+            fn run(&mut self, world: &World) -> Result<(), EcsError> {
+                // tells the compiler which of the two `FnMut` bounds is called
+                fn call<$($param),*>(mut func: impl FnMut($($param),*), $($value: $param),*) {
+                    func($($value),*)
+                }
+
+                let ($($value,)*) = <($($param,)*) as SystemParam>::fetch(&mut self.state, world)?;
+                call(&mut self.func, $($value),*);
+                Ok(())
+            }
+
+            fn apply(&mut self, world: &mut World) -> Result<(), EcsError> {
+                <($($param,)*) as SystemParam>::apply(&mut self.state, world)
+            }
+        }
+
+        impl<Func, $($param: SystemParam + 'static),*> IntoSystem<($($param,)*)> for Func
+        where
+            Func: FnMut($($param),*) + for<'w> FnMut($($param::Item<'w>),*) + Send + 'static
+        {
+            type System = FunctionSystem<Func, ($($param,)*)>;
+
+            fn into_system(self) -> Self::System {
+                let mut access = Vec::new();
+                <($($param,)*) as SystemParam>::access(&mut access);
+                FunctionSystem { func: self, name: any::type_name::<Func>(), access, state: Default::default() }
+            }
+        }
+    };
+}
+
+function_system!();
+function_system!(A a);
+function_system!(A a, B b);
+function_system!(A a, B b, C c);
+function_system!(A a, B b, C c, D d);
+function_system!(A a, B b, C c, D d, E e);
+function_system!(A a, B b, C c, D d, E e, F f);
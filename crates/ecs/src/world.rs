@@ -1,91 +1,495 @@
 // mod components;
 
-use std::cell::{ RefCell, RefMut };
+use std::any::TypeId;
+use std::error::Error;
+use std::fmt;
+
+use crate::archetype::{ Archetype, ArchetypeId, Archetypes, Location };
+use crate::cell::{ AtomicRefCell, BorrowError, Ref, RefMut };
+use crate::component::{ Component, ComponentId, Components, StorageType };
+use crate::entity::{ Entities, Entity };
+use crate::events::{ Event, Events };
+use crate::query::{ Query, QueryFilter, WorldQuery };
+use crate::resource::{ Resource, Resources };
+use crate::sparse::{ SparseSet, SparseStorage };
 
 pub struct Health(pub i32);
 pub struct Name(pub &'static str);
+pub struct Poisoned(pub i32);
+pub struct Turn(pub u32);
+pub struct Perished(pub Entity);
 
-trait ComponentVec {
-    fn as_any(&self) -> &dyn std::any::Any;
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
-    fn push_none(&mut self);
+#[derive(Debug, Clone, PartialEq)]
+pub enum EcsError {
+    // the entity was despawned, or its slot now belongs to a newer one
+    NoSuchEntity(Entity),
+    // the component type is already stored the other way
+    StorageMismatch { component: &'static str, storage: StorageType },
+    // a query asks for the component mutably and any other way at once
+    ConflictingAccess { component: &'static str },
+    // the storage of the component is borrowed mutably, by a query still alive for example
+    AlreadyBorrowed { component: &'static str },
+    // a system runs before or after a system its stage does not have
+    UnknownDependency { system: &'static str, dependency: &'static str },
+    // a system runs before or after a name that several systems of its stage share
+    AmbiguousDependency { system: &'static str, dependency: &'static str },
+    // systems of a stage wait for each other
+    DependencyCycle { system: &'static str, stage: &'static str },
+    // a system needs a resource the world does not have
+    NoSuchResource { resource: &'static str },
+    ResourceAlreadyBorrowed { resource: &'static str }
 }
 
-impl<T: 'static> ComponentVec for RefCell<Vec<Option<T>>> {
-    fn as_any(&self) -> &dyn std::any::Any {
-        self as &dyn std::any::Any
+impl fmt::Display for EcsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EcsError::NoSuchEntity(entity) => write!(f, "entity {} does not exist anymore", entity),
+            EcsError::StorageMismatch { component, storage } => write!(
+                f,
+                "component {} is already stored in {:?} storage",
+                component, storage
+            ),
+            EcsError::ConflictingAccess { component } => write!(
+                f,
+                "query accesses component {} mutably and another way at the same time",
+                component
+            ),
+            EcsError::AlreadyBorrowed { component } => write!(f, "component {} is already borrowed", component),
+            EcsError::UnknownDependency { system, dependency } => write!(
+                f,
+                "system {} depends on {}, which is not in its stage",
+                system, dependency
+            ),
+            EcsError::AmbiguousDependency { system, dependency } => write!(
+                f,
+                "system {} depends on {}, which names several systems of its stage",
+                system, dependency
+            ),
+            EcsError::DependencyCycle { system, stage } => write!(
+                f,
+                "system {} of stage {} is part of a dependency cycle",
+                system, stage
+            ),
+            EcsError::NoSuchResource { resource } => write!(f, "resource {} was never inserted", resource),
+            EcsError::ResourceAlreadyBorrowed { resource } => write!(f, "resource {} is already borrowed", resource),
+        }
     }
+}
 
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        self as &mut dyn std::any::Any
-    }
+impl Error for EcsError {}
 
-    fn push_none(&mut self) {
-        self.get_mut().push(None)
-    }
-}
+// `Events::update` for one event type
+type EventUpdate = fn(&World);
 
 pub struct World {
-    pub entities_count: usize,
-    pub component_vecs: Vec<Box<dyn ComponentVec>>,
+    entities: Entities,
+    // indexed by `Entity::index`, meaningless for free slots
+    locations: Vec<Location>,
+    components: Components,
+    archetypes: Archetypes,
+    // indexed by `ComponentId`, none for the components stored in tables
+    sparse_sets: Vec<Option<AtomicRefCell<Box<dyn SparseStorage>>>>,
+    resources: Resources,
+    // `Events::update` of every event type added, once per type
+    event_updates: Vec<(TypeId, EventUpdate)>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
-            entities_count: 0,
-            component_vecs: Vec::new(),
+            entities: Entities::default(),
+            locations: Vec::new(),
+            components: Components::default(),
+            archetypes: Archetypes::default(),
+            sparse_sets: Vec::new(),
+            resources: Resources::default(),
+            event_updates: Vec::new(),
         }
     }
 
-    pub fn new_entity(&mut self) -> usize {
-        let entity_id = self.entities_count;
-        for component_vec in self.component_vecs.iter_mut() {
-            component_vec.push_none();
+    pub fn new_entity(&mut self) -> Entity {
+        self.flush_entities();
+        let entity = self.entities.alloc();
+        self.place_empty(entity);
+        entity
+    }
+
+    // the entities reserved by `Commands::spawn` come to life without components
+    pub(crate) fn flush_entities(&mut self) {
+        let reserved: Vec<Entity> = self.entities.flush().collect();
+        for entity in reserved {
+            self.place_empty(entity);
+        }
+    }
+
+    fn place_empty(&mut self, entity: Entity) {
+        let row = self.archetypes.get_mut(ArchetypeId::EMPTY).push_entity(entity);
+
+        let location = Location { archetype: ArchetypeId::EMPTY, row };
+        match self.locations.get_mut(entity.index()) {
+            Some(slot) => *slot = location,
+            None => self.locations.push(location),
+        }
+    }
+
+    // drop every component of `entity` and give its slot back,
+    // false when it was already despawned
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.entities.free(entity) {
+            return false;
+        }
+
+        for sparse_set in self.sparse_sets.iter_mut().flatten() {
+            sparse_set.get_mut().remove_entity(entity);
+        }
+        let location = self.locations[entity.index()];
+        if let Some(moved) = self.archetypes.get_mut(location.archetype).swap_remove(location.row) {
+            self.locations[moved.index()].row = location.row;
+        }
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    pub(crate) fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    // choose where the values of `CT` are stored, before any entity gets one,
+    // the component types that are not registered go to the tables
+    pub fn register_component<CT: Component>(&mut self, storage: StorageType) -> Result<ComponentId, EcsError> {
+        let id = self.components.register_with::<CT>(storage);
+        if self.sparse_sets.len() <= id.index() {
+            let sparse_set = match storage {
+                StorageType::Table => None,
+                StorageType::SparseSet => Some(AtomicRefCell::new((self.components.info(id).new_sparse_set)())),
+            };
+            self.sparse_sets.push(sparse_set);
         }
 
-        self.entities_count += 1;
-        entity_id
+        match self.components.info(id).storage {
+            registered if registered == storage => Ok(id),
+            registered => Err(EcsError::StorageMismatch { component: self.components.info(id).name, storage: registered }),
+        }
     }
 
-    pub fn add_component_to_entity<CT: 'static>(
+    // give `entity` a component, replacing the one of the same type it had,
+    // a new table component type moves the entity to the archetype with it
+    pub fn add_component_to_entity<CT: Component>(
         &mut self,
-        entity: usize,
+        entity: Entity,
         component: CT
-    ) {
-        for component_vec in self.component_vecs.iter_mut() {
-            if let Some(component_vec) = component_vec.as_any_mut().downcast_mut::<RefCell<Vec<Option<CT>>>>() {
-                component_vec.get_mut()[entity] = Some(component);
-                return;
-            }
+    ) -> Result<(), EcsError> {
+        let location = self.location(entity)?;
+        let id = self.components.id::<CT>()
+            .map_or_else(|| self.register_component::<CT>(StorageType::Table), Ok)?;
+
+        if let Some(sparse_set) = &mut self.sparse_sets[id.index()] {
+            sparse_set.get_mut().as_any_mut().downcast_mut::<SparseSet<CT>>().unwrap().insert(entity, component);
+            return Ok(());
         }
 
-        // handle no matching components
-        let mut new_component_vec: Vec<Option<CT>> = Vec::with_capacity(self.entities_count);
-        for _ in 0..self.entities_count {
-            new_component_vec.push(None);
+        let source = self.archetypes.get_mut(location.archetype);
+        if source.contains(id) {
+            source.typed_column::<CT>(id)[location.row] = component;
+            return Ok(());
         }
-        new_component_vec[entity] = Some(component);
-        self.component_vecs.push(Box::new(RefCell::new(new_component_vec)));
+
+        let mut components = source.components().to_vec();
+        components.insert(components.binary_search(&id).unwrap_err(), id);
+        let target = self.archetypes.get_or_insert(components, &self.components);
+
+        let (source, target_archetype) = self.archetypes.pair_mut(location.archetype, target);
+        let (row, moved) = source.move_row(location.row, target_archetype, None);
+        target_archetype.typed_column::<CT>(id).push(component);
+        self.relocate(entity, Location { archetype: target, row }, moved, location.row);
+        Ok(())
     }
 
-    pub fn borrow_component_vec<CT: 'static>(&self) -> Option<&RefCell<Vec<Option<CT>>>> {
-        for component_vec in self.component_vecs.iter() {
-            if let Some(component_vec) = component_vec.as_any().downcast_ref::<RefCell<Vec<Option<CT>>>>() {
-                return Some(component_vec);
-            }
+    // take the component of type `CT` away from `entity`, none when it had none
+    pub fn remove_component_from_entity<CT: Component>(&mut self, entity: Entity) -> Result<Option<CT>, EcsError> {
+        let location = self.location(entity)?;
+        if let Some(mut sparse_set) = self.sparse_set_mut::<CT>() {
+            return Ok(sparse_set.remove(entity));
         }
+        let id = match self.components.id::<CT>() {
+            Some(id) if self.archetypes.get(location.archetype).contains(id) => id,
+            _ => return Ok(None),
+        };
+
+        let mut components = self.archetypes.get(location.archetype).components().to_vec();
+        components.retain(|&component| component != id);
+        let target = self.archetypes.get_or_insert(components, &self.components);
 
-        None
+        let (source, target_archetype) = self.archetypes.pair_mut(location.archetype, target);
+        let component = source.typed_column::<CT>(id).swap_remove(location.row);
+        let (row, moved) = source.move_row(location.row, target_archetype, Some(id));
+        self.relocate(entity, Location { archetype: target, row }, moved, location.row);
+        Ok(Some(component))
+    }
+
+    // the component of a live entity, none for a stale handle
+    pub fn component<CT: Component>(&self, entity: Entity) -> Option<Ref<'_, CT>> {
+        if let Some(sparse_set) = self.sparse_set::<CT>() {
+            return Ref::filter_map(sparse_set, |sparse_set| sparse_set.get(entity));
+        }
+        let location = self.location(entity).ok()?;
+        let column = self.archetypes.get(location.archetype).column::<CT>(self.components.id::<CT>()?)?;
+        Some(Ref::map(column, |column| &column[location.row]))
     }
 
-    pub fn borrow_component_vec_mut<CT: 'static>(&self) -> Option<RefMut<Vec<Option<CT>>>> {
-        for component_vec in self.component_vecs.iter() {
-            if let Some(component_vec) = component_vec.as_any().downcast_ref::<RefCell<Vec<Option<CT>>>>() {
-                return Some(component_vec.borrow_mut());
+    pub fn component_mut<CT: Component>(&self, entity: Entity) -> Option<RefMut<'_, CT>> {
+        if let Some(sparse_set) = self.sparse_set_mut::<CT>() {
+            return RefMut::filter_map(sparse_set, |sparse_set| sparse_set.get_mut(entity));
+        }
+        let location = self.location(entity).ok()?;
+        let column = self.archetypes.get(location.archetype).column_mut::<CT>(self.components.id::<CT>()?)?;
+        Some(RefMut::map(column, |column| &mut column[location.row]))
+    }
+
+    // the items of `Q` for every entity that has the components it requires,
+    // its storages stay borrowed until the query is dropped
+    pub fn query<Q: WorldQuery>(&self) -> Result<Query<'_, Q>, EcsError> {
+        Query::new(self)
+    }
+
+    // like `query`, only for the entities that also pass `F`, `With<T>` or `Without<T>`
+    pub fn query_filtered<Q: WorldQuery, F: QueryFilter>(&self) -> Result<Query<'_, Q, F>, EcsError> {
+        Query::new(self)
+    }
+
+    // none when `CT` is not stored in a sparse set
+    pub fn sparse_set<CT: Component>(&self) -> Option<Ref<'_, SparseSet<CT>>> {
+        let sparse_set = self.sparse_storage::<CT>()?.borrow();
+        Ref::filter_map(sparse_set, |sparse_set| sparse_set.as_any().downcast_ref::<SparseSet<CT>>())
+    }
+
+    pub fn sparse_set_mut<CT: Component>(&self) -> Option<RefMut<'_, SparseSet<CT>>> {
+        let sparse_set = self.sparse_storage::<CT>()?.borrow_mut();
+        RefMut::filter_map(sparse_set, |sparse_set| sparse_set.as_any_mut().downcast_mut::<SparseSet<CT>>())
+    }
+
+    // like `sparse_set`, an error instead of a panic when the set is borrowed mutably elsewhere
+    pub(crate) fn try_sparse_set<CT: Component>(&self) -> Result<Option<Ref<'_, SparseSet<CT>>>, BorrowError> {
+        let sparse_set = match self.sparse_storage::<CT>() {
+            Some(sparse_set) => sparse_set.try_borrow()?,
+            None => return Ok(None),
+        };
+        Ok(Ref::filter_map(sparse_set, |sparse_set| sparse_set.as_any().downcast_ref::<SparseSet<CT>>()))
+    }
+
+    pub(crate) fn try_sparse_set_mut<CT: Component>(&self) -> Result<Option<RefMut<'_, SparseSet<CT>>>, BorrowError> {
+        let sparse_set = match self.sparse_storage::<CT>() {
+            Some(sparse_set) => sparse_set.try_borrow_mut()?,
+            None => return Ok(None),
+        };
+        Ok(RefMut::filter_map(sparse_set, |sparse_set| sparse_set.as_any_mut().downcast_mut::<SparseSet<CT>>()))
+    }
+
+    // returns the `R` it replaces
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) -> Option<R> {
+        self.resources.insert(resource)
+    }
+
+    pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+        self.resources.remove()
+    }
+
+    // none when there is no `R`, panics like `component` when it is borrowed mutably
+    pub fn resource<R: Resource>(&self) -> Option<Ref<'_, R>> {
+        self.resources.try_get().expect("already mutably borrowed")
+    }
+
+    pub fn resource_mut<R: Resource>(&self) -> Option<RefMut<'_, R>> {
+        self.resources.try_get_mut().expect("already borrowed")
+    }
+
+    // the `Events<E>` resource, updated with the others by `update_events`,
+    // an `Events<E>` already inserted as a resource is kept and updated too
+    pub fn add_event<E: Event>(&mut self) {
+        if !self.resources.contains::<Events<E>>() {
+            self.insert_resource(Events::<E>::default());
+        }
+        if self.event_updates.iter().any(|&(event, _)| event == TypeId::of::<E>()) {
+            return;
+        }
+        // skipped once the resource is removed
+        self.event_updates.push((TypeId::of::<E>(), |world| {
+            if let Ok(Some(mut events)) = world.resources.try_get_mut::<Events<E>>() {
+                events.update();
             }
+        }));
+    }
+
+    // at the end of every tick, `Schedule::run` does it even when a system fails
+    pub fn update_events(&mut self) {
+        for (_, update) in self.event_updates.iter() {
+            update(self);
         }
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    pub fn components(&self) -> &Components {
+        &self.components
+    }
+
+    // the archetypes having at least all of `components`, sparse set components are in none
+    pub fn archetypes_with<'w>(&'w self, components: &'w [ComponentId]) -> impl Iterator<Item = &'w Archetype> {
+        self.archetypes.iter()
+            .filter(move |archetype| components.iter().all(|&component| archetype.contains(component)))
+    }
+
+    fn sparse_storage<CT: Component>(&self) -> Option<&AtomicRefCell<Box<dyn SparseStorage>>> {
+        self.sparse_sets.get(self.components.id::<CT>()?.index())?.as_ref()
+    }
+
+    fn location(&self, entity: Entity) -> Result<Location, EcsError> {
+        match self.entities.contains(entity) {
+            true => Ok(self.locations[entity.index()]),
+            false => Err(EcsError::NoSuchEntity(entity)),
+        }
+    }
+
+    // `entity` was moved to `location`, `moved` took the row it left behind
+    fn relocate(&mut self, entity: Entity, location: Location, moved: Option<Entity>, row: usize) {
+        self.locations[entity.index()] = location;
+        if let Some(moved) = moved {
+            self.locations[moved.index()].row = row;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[test]
+    fn despawned_entity_loses_its_components_and_its_slot() {
+        let mut world = World::new();
+        let gone = world.new_entity();
+        let kept = world.new_entity();
+        world.add_component_to_entity(gone, Position(1)).unwrap();
+        world.add_component_to_entity(kept, Position(2)).unwrap();
+
+        assert!(world.despawn(gone));
+        assert!(!world.despawn(gone));
+        assert!(!world.is_alive(gone));
+        assert!(world.component::<Position>(gone).is_none());
+        assert_eq!(world.add_component_to_entity(gone, Position(3)), Err(EcsError::NoSuchEntity(gone)));
+        assert_eq!(world.remove_component_from_entity::<Position>(gone), Err(EcsError::NoSuchEntity(gone)));
+        // the entity that took its row keeps its component
+        assert_eq!(*world.component::<Position>(kept).unwrap(), Position(2));
+
+        let reused = world.new_entity();
+        assert_eq!(reused.index(), gone.index());
+        assert_eq!(reused.generation(), gone.generation() + 1);
+        assert!(world.component::<Position>(reused).is_none());
+        assert!(world.component::<Position>(gone).is_none());
+    }
+
+    #[test]
+    fn adding_and_removing_moves_the_entity_between_archetypes() {
+        let mut world = World::new();
+        let first = world.new_entity();
+        let second = world.new_entity();
+        for (entity, value) in [(first, 1), (second, 2)] {
+            world.add_component_to_entity(entity, Position(value)).unwrap();
+            world.add_component_to_entity(entity, Health(value * 10)).unwrap();
+        }
+        let position = world.components().id::<Position>().unwrap();
+        let health = world.components().id::<Health>().unwrap();
+        let counts = |world: &World| {
+            let both: usize = world.archetypes_with(&[position, health]).map(|archetype| archetype.len()).sum();
+            let positioned: usize = world.archetypes_with(&[position]).map(|archetype| archetype.len()).sum();
+            (both, positioned)
+        };
+        assert_eq!(counts(&world), (2, 2));
+
+        world.add_component_to_entity(first, Name("first")).unwrap();
+        let removed = world.remove_component_from_entity::<Health>(first).unwrap();
+        assert_eq!(removed.map(|health| health.0), Some(10));
+        assert_eq!(counts(&world), (1, 2));
+        // replacing a component keeps the entity where it is
+        world.add_component_to_entity(second, Position(3)).unwrap();
+        assert_eq!(counts(&world), (1, 2));
+
+        // the values of the other components move along
+        assert_eq!(*world.component::<Position>(first).unwrap(), Position(1));
+        assert_eq!(world.component::<Name>(first).unwrap().0, "first");
+        assert!(world.component::<Health>(first).is_none());
+        assert_eq!(*world.component::<Position>(second).unwrap(), Position(3));
+        assert_eq!(world.component::<Health>(second).unwrap().0, 20);
+        assert!(world.remove_component_from_entity::<Health>(first).unwrap().is_none());
+    }
+
+    #[test]
+    fn sparse_set_components_never_move_the_entity() {
+        let mut world = World::new();
+        let poisoned = world.register_component::<Poisoned>(StorageType::SparseSet).unwrap();
+        assert_eq!(world.register_component::<Poisoned>(StorageType::SparseSet), Ok(poisoned));
+        assert_eq!(
+            world.register_component::<Poisoned>(StorageType::Table),
+            Err(EcsError::StorageMismatch { component: world.components().info(poisoned).name, storage: StorageType::SparseSet })
+        );
+
+        let first = world.new_entity();
+        let second = world.new_entity();
+        world.add_component_to_entity(first, Position(1)).unwrap();
+        world.add_component_to_entity(first, Poisoned(3)).unwrap();
+        world.add_component_to_entity(second, Poisoned(5)).unwrap();
+        world.add_component_to_entity(second, Poisoned(6)).unwrap();
+        assert_eq!(world.archetypes_with(&[poisoned]).count(), 0);
+        let position = world.components().id::<Position>().unwrap();
+        let archetypes = world.archetypes_with(&[position]).count();
+
+        assert_eq!(world.component::<Poisoned>(first).unwrap().0, 3);
+        assert_eq!(world.remove_component_from_entity::<Poisoned>(first).unwrap().map(|poison| poison.0), Some(3));
+        assert!(world.remove_component_from_entity::<Poisoned>(first).unwrap().is_none());
+        assert_eq!(world.archetypes_with(&[position]).count(), archetypes);
+        assert_eq!(*world.component::<Position>(first).unwrap(), Position(1));
+
+        world.component_mut::<Poisoned>(second).unwrap().0 -= 1;
+        assert_eq!(world.sparse_set::<Poisoned>().unwrap().values().iter().map(|poison| poison.0).collect::<Vec<_>>(), [5]);
+        world.despawn(second);
+        assert!(world.sparse_set::<Poisoned>().unwrap().is_empty());
+        assert!(world.sparse_set::<Position>().is_none());
+    }
+
+    #[test]
+    fn events_are_updated_once_even_when_inserted_or_removed() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        events.send(Turn(1));
+        world.insert_resource(events);
+        world.add_event::<Turn>();
+        world.add_event::<Turn>();
+        assert_eq!(world.resource::<Events<Turn>>().unwrap().len(), 1);
+
+        // updated once per call, the event is dropped by the second update
+        world.update_events();
+        assert_eq!(world.resource::<Events<Turn>>().unwrap().len(), 1);
+        world.update_events();
+        assert!(world.resource::<Events<Turn>>().unwrap().is_empty());
 
-        None
+        assert!(world.remove_resource::<Events<Turn>>().is_some());
+        world.update_events();
+        world.add_event::<Turn>();
+        world.resource_mut::<Events<Turn>>().unwrap().send(Turn(2));
+        world.update_events();
+        assert_eq!(world.resource::<Events<Turn>>().unwrap().len(), 1);
     }
 }
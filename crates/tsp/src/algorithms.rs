@@ -1,3 +1,211 @@
+use std::slice;
+
+use rand::RngCore;
+
+use crate::route::Route;
+use crate::observer::{ SolverEvent, SolverObserver };
+use crate::neighbours::nearest_neighbours;
+use crate::utils::Strategy;
+
+// closest cities the neighbour strategy tries to connect to
+const NEIGHBOURS: usize = 10;
+
+// keep swapping edges until no swap shortens the route anymore
+pub fn edges_swap(route: &mut Route, rng: &mut dyn RngCore, observer: &mut dyn SolverObserver) {
+    edges_swap_with(route, Strategy::Shuffled, rng, observer);
+}
+
+// the edge swap looking for improving swaps in the order of `strategy`,
+// returns how many passes over the route it took
+pub fn edges_swap_with(
+    route: &mut Route,
+    strategy: Strategy,
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> usize {
+    let mut search = match strategy {
+        Strategy::Shuffled => None,
+        _ => Some(DontLook::new(route, strategy)),
+    };
+    let mut iteration = 0;
+
+    loop {
+        iteration += 1;
+        observer.notify(&SolverEvent::Iteration { iteration, distance: route.total_distance() });
+
+        let improved = match &mut search {
+            Some(search) => search.pass(route, observer),
+            None => edges_swap_pass(route, rng, observer),
+        };
+        if !improved {
+            break;
+        }
+    }
+
+    observer.notify(&SolverEvent::Finished {
+        tours: slice::from_ref(route),
+        distance: route.total_distance()
+    });
+    iteration
+}
+
+// a single pass over the pairings, stops at the first swap that shortens the route
+pub fn edges_swap_pass(route: &mut Route, rng: &mut dyn RngCore, observer: &mut dyn SolverObserver) -> bool {
+    let pairings = &route.possible_pairings(rng);
+
+    for pair in pairings {
+        if route.should_edges_swap(pair.0, pair.1) {
+            observer.notify(&SolverEvent::Improvement {
+                tours: slice::from_ref(route),
+                distance: route.total_distance()
+            });
+            return true;
+        }
+    }
+
+    false
+}
+
+// the deterministic strategies, every stop keeps a don't-look bit that is set once none of
+// its swaps shortens the route and cleared when one of its edges changes, passes skip those stops;
+// stops are numbered by their position when the search starts
+struct DontLook {
+    strategy: Strategy,
+    // stop at every position and position of every stop
+    at: Vec<usize>,
+    position: Vec<usize>,
+    dont_look: Vec<bool>,
+    neighbours: Vec<Vec<usize>>
+}
+
+impl DontLook {
+    fn new(route: &Route, strategy: Strategy) -> DontLook {
+        let length = route.routes.len();
+        let neighbours = match strategy {
+            Strategy::Neighbour => nearest_neighbours(&route.routes, NEIGHBOURS.min(length.saturating_sub(1))),
+            _ => vec![],
+        };
+
+        DontLook {
+            strategy,
+            at: (0..length).collect(),
+            position: (0..length).collect(),
+            dont_look: vec![false; length],
+            neighbours
+        }
+    }
+
+    fn pass(&mut self, route: &mut Route, observer: &mut dyn SolverObserver) -> bool {
+        let length = route.routes.len();
+        if length < 4 {
+            return false;
+        }
+
+        let mut best: Option<(f32, usize, usize)> = None;
+        let mut improved = false;
+
+        for position in 0..length {
+            let stop = self.at[position];
+            if self.dont_look[stop] {
+                continue;
+            }
+
+            let found = match self.strategy {
+                Strategy::Neighbour => self.neighbour_swap(route, stop),
+                _ => self.best_swap(route, position),
+            };
+            let (gain, a, b) = match found {
+                Some(found) => found,
+                None => {
+                    self.dont_look[stop] = true;
+                    continue;
+                },
+            };
+
+            match self.strategy {
+                Strategy::Best => {
+                    if best.is_none_or(|best| gain > best.0) {
+                        best = Some((gain, a, b));
+                    }
+                },
+                _ => {
+                    self.swap(route, a, b, observer);
+                    improved = true;
+                },
+            }
+        }
+
+        // best improvement makes a single swap per pass
+        if let Some((_, a, b)) = best {
+            self.swap(route, a, b, observer);
+            improved = true;
+        }
+
+        improved
+    }
+
+    // the swap with the highest gain among those replacing either edge of the stop at `position`,
+    // first improvement takes it as soon as the stop has any
+    fn best_swap(&self, route: &Route, position: usize) -> Option<(f32, usize, usize)> {
+        let length = route.routes.len();
+        let mut best: Option<(f32, usize, usize)> = None;
+
+        // the edge ending at the stop, then the one leaving it
+        for a in [position, (position + 1) % length] {
+            for b in 0..length {
+                match route.edges_swap_gain(a, b) {
+                    Some(gain) if gain > 0.0 && best.is_none_or(|best| gain > best.0) => {
+                        best = Some((gain, a, b));
+                        if self.strategy == Strategy::First {
+                            return best;
+                        }
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        best
+    }
+
+    // the first swap connecting `stop` to one of its closest stops that shortens the route
+    fn neighbour_swap(&self, route: &Route, stop: usize) -> Option<(f32, usize, usize)> {
+        let length = route.routes.len();
+        let a = self.position[stop];
+
+        for &other in &self.neighbours[stop] {
+            let b = self.position[other];
+            // dropping the edges ending at both stops, then the edges leaving them
+            for (a, b) in [(a, b), ((a + 1) % length, (b + 1) % length)] {
+                match route.edges_swap_gain(a, b) {
+                    Some(gain) if gain > 0.0 => return Some((gain, a, b)),
+                    _ => (),
+                }
+            }
+        }
+
+        None
+    }
+
+    fn swap(&mut self, route: &mut Route, a: usize, b: usize, observer: &mut dyn SolverObserver) {
+        let length = route.routes.len();
+        for position in [a, b, (a + length - 1) % length, (b + length - 1) % length] {
+            self.dont_look[self.at[position]] = false;
+        }
+
+        route.swap_edges(a, b);
+        let (lower, higher) = (a.min(b), a.max(b));
+        self.at[lower..higher].reverse();
+        for position in lower..higher {
+            self.position[self.at[position]] = position;
+        }
+
+        observer.notify(&SolverEvent::Improvement {
+            tours: slice::from_ref(route),
+            distance: route.total_distance()
+        });
+    }
+}
 
 pub fn simmulated_annealing() {
     println!("Simmulated Annealing");
@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::slice;
+use std::time::Instant;
+
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use serde::{ Serialize, Deserialize };
+
+use crate::algorithms::edges_swap_pass;
+use crate::observer::{ SolverEvent, SolverObserver };
+use crate::result;
+use crate::route::Route;
+use crate::utils::{ Config, generate_cities };
+
+// everything the edge swap needs to carry on from the start of a pass,
+// resuming from it gives the same tour as a run that never stopped
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub config: Config,
+    pub route: Route,
+    pub rng: Pcg64,
+    // passes finished so far
+    pub iteration: usize,
+    // seconds spent solving before this checkpoint
+    pub runtime: f64
+}
+
+impl Checkpoint {
+    // a fresh run, the cities come from the seed like any other run
+    pub fn new(config: &Config) -> Checkpoint {
+        let mut rng = Pcg64::seed_from_u64(config.seed);
+        let route = Route::new(generate_cities(config, &mut rng));
+
+        Checkpoint {
+            config: config.clone(),
+            route,
+            rng,
+            iteration: 0,
+            runtime: 0.0
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Checkpoint, Box<dyn Error>> {
+        result::load(path)
+    }
+
+    // written next to `path` first, so a run killed while saving
+    // still leaves the previous checkpoint behind,
+    // the extension is kept since it picks the format
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let partial = match Path::new(path).extension() {
+            Some(extension) => Path::new(path).with_extension(format!("partial.{}", extension.to_string_lossy())),
+            None => Path::new(path).with_extension("partial"),
+        };
+        let partial = partial.to_string_lossy();
+        result::save(self, &partial)?;
+        fs::rename(&*partial, path)?;
+
+        Ok(())
+    }
+}
+
+// `edges_swap` that saves the search to `path` every `every` passes and once it is done
+pub fn checkpointed_edges_swap(
+    checkpoint: &mut Checkpoint,
+    path: &str,
+    every: usize,
+    observer: &mut dyn SolverObserver
+) -> Result<(), Box<dyn Error>> {
+    let mut started = Instant::now();
+
+    loop {
+        if checkpoint.iteration > 0 && checkpoint.iteration.is_multiple_of(every) {
+            checkpoint.runtime += started.elapsed().as_secs_f64();
+            started = Instant::now();
+            checkpoint.save(path)?;
+        }
+
+        checkpoint.iteration += 1;
+        observer.notify(&SolverEvent::Iteration {
+            iteration: checkpoint.iteration,
+            distance: checkpoint.route.total_distance()
+        });
+
+        if !edges_swap_pass(&mut checkpoint.route, &mut checkpoint.rng, observer) {
+            break;
+        }
+    }
+
+    checkpoint.runtime += started.elapsed().as_secs_f64();
+    checkpoint.save(path)?;
+
+    observer.notify(&SolverEvent::Finished {
+        tours: slice::from_ref(&checkpoint.route),
+        distance: checkpoint.route.total_distance()
+    });
+    Ok(())
+}
@@ -0,0 +1,378 @@
+use std::fmt;
+use std::slice;
+
+use minilp::{ ComparisonOp, LinearExpr, OptimizationDirection, Problem, Solution, Variable };
+use rand::RngCore;
+
+use crate::algorithms::edges_swap;
+use crate::observer::{ Silent, SolverEvent, SolverObserver };
+use crate::route::Route;
+
+const EPSILON: f64 = 1e-6;
+// 2-opt runs giving the first upper bound
+const STARTS: usize = 4;
+
+pub struct ExactReport {
+    pub route: Route,
+    // no tour is shorter than this, equal to the route's length once the search is complete
+    pub lower_bound: f64,
+    pub optimal: bool,
+    pub nodes: usize,
+    pub cuts: usize
+}
+
+impl fmt::Display for ExactReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Tour : {:?}", self.route.indices)?;
+        writeln!(f, "Distance : {}", self.route.total_distance())?;
+        writeln!(f, "Lower Bound : {}", self.lower_bound)?;
+        writeln!(f, "Branch Nodes : {}", self.nodes)?;
+        writeln!(f, "Subtour Cuts : {}", self.cuts)?;
+        match self.optimal {
+            true => write!(f, "Proven optimal"),
+            false => write!(f, "Stopped after {} nodes, the tour may not be optimal", self.nodes),
+        }
+    }
+}
+
+// branch and cut over the LP relaxation with one variable per edge,
+// every city has two tour edges and every subset of cities is left by at least two of them,
+// those subtour cuts are added as the LP solutions break them
+pub fn cutting_plane(
+    route: &Route,
+    max_nodes: usize,
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> ExactReport {
+    let length = route.routes.len();
+    let mut best = upper_bound(route, rng);
+    let mut upper = best.total_distance() as f64;
+    observer.notify(&SolverEvent::Improvement { tours: slice::from_ref(&best), distance: best.total_distance() });
+
+    let mut problem = Problem::new(OptimizationDirection::Minimize);
+    let mut edges = vec![];
+    let mut vars = vec![];
+    for a in 0..length {
+        for b in a + 1..length {
+            edges.push((a, b));
+            vars.push(problem.add_var(route.distance(a, b) as f64, (0.0, 1.0)));
+        }
+    }
+    for city in 0..length {
+        let mut degree = LinearExpr::empty();
+        for (&(a, b), &var) in edges.iter().zip(&vars) {
+            if a == city || b == city {
+                degree.add(var, 1.0);
+            }
+        }
+        problem.add_constraint(degree, ComparisonOp::Eq, 2.0);
+    }
+
+    let mut search = Search { length, edges, vars, pool: vec![], cuts: 0 };
+    let mut stack = vec![];
+    if length > 3 {
+        if let Ok(solution) = problem.solve() {
+            stack.push(Node { solution, bound: 0.0, branch: None });
+        }
+    }
+    let mut nodes = 0;
+
+    while nodes < max_nodes {
+        let Node { solution, branch, .. } = match stack.pop() {
+            Some(node) => node,
+            None => break,
+        };
+        nodes += 1;
+
+        let solution = match branch {
+            Some((var, value)) => solution.fix_var(var, value),
+            None => Ok(solution),
+        };
+        let solution = match solution.and_then(|solution| search.cut(solution, upper)) {
+            Ok(solution) => solution,
+            Err(_) => continue,
+        };
+        observer.notify(&SolverEvent::Iteration { iteration: nodes, distance: upper as f32 });
+        if solution.objective() >= upper - EPSILON {
+            continue;
+        }
+
+        match search.fractional(&solution) {
+            Some(var) => {
+                // the LP often points at a better tour than the current one
+                let mut rounded = route.reordered(&search.rounded_tour(&solution));
+                edges_swap(&mut rounded, rng, &mut Silent);
+                if (rounded.total_distance() as f64) < upper - EPSILON {
+                    best = rounded;
+                    upper = best.total_distance() as f64;
+                    observer.notify(&SolverEvent::Improvement { tours: slice::from_ref(&best), distance: best.total_distance() });
+                    if solution.objective() >= upper - EPSILON {
+                        continue;
+                    }
+                }
+
+                let bound = solution.objective();
+                // leaving the edge out first finds good tours sooner
+                stack.push(Node { solution: solution.clone(), bound, branch: Some((var, 1.0)) });
+                stack.push(Node { solution, bound, branch: Some((var, 0.0)) });
+            },
+            None => {
+                best = route.reordered(&search.tour(&solution));
+                upper = best.total_distance() as f64;
+                observer.notify(&SolverEvent::Improvement { tours: slice::from_ref(&best), distance: best.total_distance() });
+            },
+        }
+    }
+
+    let optimal = stack.is_empty();
+    let lower_bound = stack.iter().map(|node| node.bound).fold(upper, f64::min);
+    observer.notify(&SolverEvent::Finished { tours: slice::from_ref(&best), distance: best.total_distance() });
+
+    ExactReport { route: best, lower_bound, optimal, nodes, cuts: search.cuts }
+}
+
+// shortest of a few 2-opt runs, anything the branch and cut finds has to beat it
+fn upper_bound(route: &Route, rng: &mut dyn RngCore) -> Route {
+    (0..STARTS)
+        .map(|_| {
+            let mut start = route.clone();
+            edges_swap(&mut start, rng, &mut Silent);
+            start
+        })
+        .min_by(|a, b| a.total_distance().partial_cmp(&b.total_distance()).unwrap())
+        .unwrap()
+}
+
+// waiting to be solved: the parent's solution and bound, and the edge to fix
+struct Node {
+    solution: Solution,
+    bound: f64,
+    branch: Option<(Variable, f64)>
+}
+
+struct Search {
+    length: usize,
+    edges: Vec<(usize, usize)>,
+    vars: Vec<Variable>,
+    // every subtour cut found so far, as its set of cities
+    pool: Vec<Vec<bool>>,
+    cuts: usize
+}
+
+impl Search {
+    // add violated subtour cuts until there are none left or the bound passes `upper`
+    fn cut(&mut self, mut solution: Solution, upper: f64) -> Result<Solution, minilp::Error> {
+        while solution.objective() < upper - EPSILON {
+            let violated = self.violated(&solution);
+            if violated.is_empty() {
+                break;
+            }
+
+            for inside in violated {
+                let mut leaving = LinearExpr::empty();
+                for (&(a, b), &var) in self.edges.iter().zip(&self.vars) {
+                    if inside[a] != inside[b] {
+                        leaving.add(var, 1.0);
+                    }
+                }
+                solution = solution.add_constraint(leaving, ComparisonOp::Ge, 2.0)?;
+            }
+        }
+
+        Ok(solution)
+    }
+
+    // sets of cities left by less than two edges of `solution`,
+    // cheapest checks first: known cuts, disconnected parts, then minimum cuts
+    fn violated(&mut self, solution: &Solution) -> Vec<Vec<bool>> {
+        let weights = self.weights(solution);
+        let crossing = |inside: &[bool]| -> f64 {
+            self.edges.iter()
+                .filter(|&&(a, b)| inside[a] != inside[b])
+                .map(|&(a, b)| weights[a][b])
+                .sum()
+        };
+
+        let known: Vec<Vec<bool>> = self.pool.iter()
+            .filter(|inside| crossing(inside) < 2.0 - EPSILON)
+            .cloned()
+            .collect();
+        if !known.is_empty() {
+            return known;
+        }
+
+        let components = components(&weights);
+        let sides = if components.iter().any(|&component| component != components[0]) {
+            let mut roots = components.clone();
+            roots.sort_unstable();
+            roots.dedup();
+            roots.iter()
+                .map(|&root| (0..self.length).filter(|&city| components[city] == root).collect())
+                .collect()
+        } else {
+            let (value, side) = minimum_cut(&weights);
+            if value >= 2.0 - EPSILON {
+                return vec![];
+            }
+            vec![side]
+        };
+
+        let found: Vec<Vec<bool>> = sides.into_iter()
+            .map(|side: Vec<usize>| {
+                let mut inside = vec![false; self.length];
+                for city in side {
+                    inside[city] = true;
+                }
+                inside
+            })
+            .collect();
+        self.cuts += found.len();
+        self.pool.extend(found.iter().cloned());
+        found
+    }
+
+    // value of every edge in `solution` as a symmetric matrix
+    fn weights(&self, solution: &Solution) -> Vec<Vec<f64>> {
+        let mut weights = vec![vec![0.0; self.length]; self.length];
+        for (&(a, b), &var) in self.edges.iter().zip(&self.vars) {
+            weights[a][b] = *solution.var_value(var);
+            weights[b][a] = weights[a][b];
+        }
+
+        weights
+    }
+
+    // the edge to branch on, the one furthest from being decided
+    fn fractional(&self, solution: &Solution) -> Option<Variable> {
+        self.vars.iter()
+            .map(|&var| (var, (solution.var_value(var) - 0.5).abs()))
+            .filter(|&(_, distance)| distance < 0.5 - EPSILON)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(var, _)| var)
+    }
+
+    // greedy tour taking the edges with the highest values first, cheaper ones on ties
+    fn rounded_tour(&self, solution: &Solution) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.edges.len()).collect();
+        order.sort_by(|&a, &b| solution.var_value(self.vars[b]).partial_cmp(solution.var_value(self.vars[a])).unwrap());
+
+        let mut degree = vec![0; self.length];
+        let mut path_end: Vec<usize> = (0..self.length).collect();
+        let mut neighbours = vec![vec![]; self.length];
+        let mut joined = 0;
+        for edge in order {
+            let (a, b) = self.edges[edge];
+            // joining two path ends, unless they are the ends of the same path
+            if degree[a] < 2 && degree[b] < 2 && path_end[a] != b {
+                let (end_a, end_b) = (path_end[a], path_end[b]);
+                path_end[end_a] = end_b;
+                path_end[end_b] = end_a;
+                degree[a] += 1;
+                degree[b] += 1;
+                neighbours[a].push(b);
+                neighbours[b].push(a);
+                joined += 1;
+                if joined == self.length - 1 {
+                    break;
+                }
+            }
+        }
+
+        // walk the single path left from one of its ends
+        let start = (0..self.length).find(|&city| degree[city] < 2).unwrap_or(0);
+        let mut tour = vec![start];
+        let mut prev = usize::MAX;
+        while let Some(&next) = neighbours[*tour.last().unwrap()].iter().find(|&&next| next != prev) {
+            prev = *tour.last().unwrap();
+            tour.push(next);
+        }
+
+        tour
+    }
+
+    // positions in visiting order of an integral solution without subtours
+    fn tour(&self, solution: &Solution) -> Vec<usize> {
+        let weights = self.weights(solution);
+        let mut tour = vec![0];
+        let mut visited = vec![false; self.length];
+        visited[0] = true;
+
+        while tour.len() < self.length {
+            let last = *tour.last().unwrap();
+            let next = (0..self.length).find(|&city| !visited[city] && weights[last][city] > 0.5).unwrap();
+            visited[next] = true;
+            tour.push(next);
+        }
+
+        tour
+    }
+}
+
+// component number of every city over the edges used by the solution
+fn components(weights: &[Vec<f64>]) -> Vec<usize> {
+    let mut component = vec![usize::MAX; weights.len()];
+
+    for start in 0..weights.len() {
+        if component[start] != usize::MAX {
+            continue;
+        }
+        component[start] = start;
+        let mut stack = vec![start];
+        while let Some(city) = stack.pop() {
+            for (next, &weight) in weights[city].iter().enumerate() {
+                if weight > EPSILON && component[next] == usize::MAX {
+                    component[next] = start;
+                    stack.push(next);
+                }
+            }
+        }
+    }
+
+    component
+}
+
+// stoer-wagner global minimum cut, its value and the cities on one side
+fn minimum_cut(weights: &[Vec<f64>]) -> (f64, Vec<usize>) {
+    let mut weights = weights.to_vec();
+    let mut groups: Vec<Vec<usize>> = (0..weights.len()).map(|city| vec![city]).collect();
+    let mut active: Vec<usize> = (0..weights.len()).collect();
+    let mut best = (f64::INFINITY, vec![]);
+
+    while active.len() > 1 {
+        // add the most tightly connected city until all are in, the last one
+        // against everything else is the cut of the phase
+        let mut added = vec![false; weights.len()];
+        let mut connection = vec![0.0; weights.len()];
+        let (mut prev, mut last) = (active[0], active[0]);
+
+        for _ in 0..active.len() {
+            let next = *active.iter()
+                .filter(|&&city| !added[city])
+                .max_by(|&&a, &&b| connection[a].partial_cmp(&connection[b]).unwrap())
+                .unwrap();
+            added[next] = true;
+            prev = last;
+            last = next;
+            for &city in &active {
+                if !added[city] {
+                    connection[city] += weights[next][city];
+                }
+            }
+        }
+
+        if connection[last] < best.0 {
+            best = (connection[last], groups[last].clone());
+        }
+
+        // merge the last two cities of the phase
+        let merged = std::mem::take(&mut groups[last]);
+        groups[prev].extend(merged);
+        for &city in &active {
+            weights[prev][city] += weights[last][city];
+            weights[city][prev] = weights[prev][city];
+        }
+        active.retain(|&city| city != last);
+    }
+
+    best
+}
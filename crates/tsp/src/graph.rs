@@ -0,0 +1,343 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+type GraphResult<T> = Result<T, Box<dyn Error>>;
+
+// weighted road network, nodes are numbered from 0
+pub struct Graph {
+    // node coordinates, only known when a DIMACS `.co` file sits next to the `.gr` file
+    pub coordinates: Option<Vec<(i32, i32)>>,
+    adjacency: Vec<Vec<(usize, f32)>>
+}
+
+impl Graph {
+    // `.gr` files are read as DIMACS shortest path graphs,
+    // anything else as a `from,to,weight` edge list
+    pub fn from_file(path: &str) -> GraphResult<Graph> {
+        let text = fs::read_to_string(path)?;
+        let path = Path::new(path);
+
+        if path.extension().is_some_and(|extension| extension == "gr") {
+            let mut graph = Graph::from_dimacs(&text)?;
+            let coordinates = path.with_extension("co");
+            if coordinates.exists() {
+                graph.coordinates = Some(read_dimacs_coordinates(
+                    &fs::read_to_string(coordinates)?,
+                    graph.node_count()
+                )?);
+            }
+            Ok(graph)
+        } else {
+            Graph::from_edge_list(&text)
+        }
+    }
+
+    // DIMACS format: `p sp <nodes> <arcs>` then one `a <from> <to> <weight>` per arc,
+    // nodes are numbered from 1 and arcs are directed
+    pub fn from_dimacs(text: &str) -> GraphResult<Graph> {
+        let mut adjacency: Option<Vec<Vec<(usize, f32)>>> = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.first() {
+                Some(&"p") => {
+                    let nodes = parse_field(&fields, 2, number)?;
+                    adjacency = Some(vec![vec![]; nodes]);
+                },
+                Some(&"a") => {
+                    let adjacency = adjacency.as_mut()
+                        .ok_or(format!("line {}: arc before the `p sp` problem line", number + 1))?;
+                    let from: usize = parse_field(&fields, 1, number)?;
+                    let to: usize = parse_field(&fields, 2, number)?;
+                    let weight = parse_weight(&fields, 3, number)?;
+                    if from == 0 || to == 0 || from > adjacency.len() || to > adjacency.len() {
+                        return Err(format!("line {}: node out of range", number + 1).into());
+                    }
+                    adjacency[from - 1].push((to - 1, weight));
+                },
+                _ => {},
+            }
+        }
+
+        match adjacency {
+            Some(adjacency) => Ok(Graph { coordinates: None, adjacency }),
+            None => Err("missing `p sp` problem line".into()),
+        }
+    }
+
+    // `from,to,weight` per line, nodes are numbered from 0 and roads go both ways,
+    // a header line or `#` comments are skipped
+    pub fn from_edge_list(text: &str) -> GraphResult<Graph> {
+        let mut adjacency: Vec<Vec<(usize, f32)>> = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if number == 0 && fields[0].parse::<usize>().is_err() {
+                continue;
+            }
+
+            let from: usize = parse_field(&fields, 0, number)?;
+            let to: usize = parse_field(&fields, 1, number)?;
+            let weight = parse_weight(&fields, 2, number)?;
+            if adjacency.len() <= from.max(to) {
+                adjacency.resize(from.max(to) + 1, vec![]);
+            }
+            adjacency[from].push((to, weight));
+            adjacency[to].push((from, weight));
+        }
+
+        Ok(Graph { coordinates: None, adjacency })
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    // shortest distance from `source` to every node and the node before it on that path,
+    // stops early once `target` is settled
+    pub fn dijkstra(&self, source: usize, target: Option<usize>) -> (Vec<f32>, Vec<Option<usize>>) {
+        let mut distances = vec![f32::INFINITY; self.node_count()];
+        let mut previous = vec![None; self.node_count()];
+        let mut queue = BinaryHeap::new();
+
+        distances[source] = 0.0;
+        queue.push(Visit { node: source, distance: 0.0 });
+
+        while let Some(Visit { node, distance }) = queue.pop() {
+            if distance > distances[node] {
+                continue;
+            }
+            if Some(node) == target {
+                break;
+            }
+
+            for &(next, weight) in &self.adjacency[node] {
+                let through = distance + weight;
+                if through < distances[next] {
+                    distances[next] = through;
+                    previous[next] = Some(node);
+                    queue.push(Visit { node: next, distance: through });
+                }
+            }
+        }
+
+        (distances, previous)
+    }
+
+    // nodes on the shortest path from `source` to `target`, both included
+    pub fn shortest_path(&self, source: usize, target: usize) -> Option<Vec<usize>> {
+        let (distances, previous) = self.dijkstra(source, Some(target));
+        if distances[target].is_infinite() {
+            return None;
+        }
+
+        let mut path = vec![target];
+        let mut node = target;
+        while let Some(prev) = previous[node] {
+            path.push(prev);
+            node = prev;
+        }
+        path.reverse();
+
+        Some(path)
+    }
+
+    // travel cost between every pair of `stops`, taken over the shortest paths of the graph
+    pub fn metric_closure(&self, stops: &[usize]) -> GraphResult<Vec<Vec<f32>>> {
+        if let Some(stop) = stops.iter().find(|&&stop| stop >= self.node_count()) {
+            return Err(format!("stop {} is not a node of the graph", stop).into());
+        }
+        let mut matrix = vec![vec![0.0; stops.len()]; stops.len()];
+
+        for (i, &stop) in stops.iter().enumerate() {
+            let (distances, _) = self.dijkstra(stop, None);
+            for (j, &other) in stops.iter().enumerate() {
+                if distances[other].is_infinite() {
+                    return Err(format!("stop {} can not be reached from stop {}", other, stop).into());
+                }
+                matrix[i][j] = distances[other];
+            }
+        }
+
+        // the edge swap assumes symmetric costs, one way arcs are averaged out,
+        // `path_length` of the expanded tour is the real cost
+        let symmetric = (0..stops.len())
+            .map(|i| (0..stops.len()).map(|j| (matrix[i][j] + matrix[j][i]) / 2.0).collect())
+            .collect();
+
+        Ok(symmetric)
+    }
+
+    // full node path of the closed tour going through `tour` stops in order
+    pub fn expand_tour(&self, tour: &[usize]) -> GraphResult<Vec<usize>> {
+        let mut path = vec![];
+
+        for (i, &stop) in tour.iter().enumerate() {
+            let next = tour[(i + 1) % tour.len()];
+            let leg = self.shortest_path(stop, next)
+                .ok_or(format!("stop {} can not be reached from stop {}", next, stop))?;
+            // every leg starts where the previous one ended
+            path.extend_from_slice(&leg[..leg.len() - 1]);
+        }
+        path.extend(tour.first());
+
+        Ok(path)
+    }
+
+    pub fn path_length(&self, path: &[usize]) -> f32 {
+        path.windows(2)
+            .map(|leg| {
+                self.adjacency[leg[0]].iter()
+                    .filter(|(to, _)| *to == leg[1])
+                    .map(|(_, weight)| *weight)
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .sum()
+    }
+}
+
+// DIMACS coordinates: one `v <node> <x> <y>` per node, numbered from 1
+fn read_dimacs_coordinates(text: &str, nodes: usize) -> GraphResult<Vec<(i32, i32)>> {
+    let mut coordinates = vec![(0, 0); nodes];
+
+    for (number, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first() == Some(&"v") {
+            let node: usize = parse_field(&fields, 1, number)?;
+            if node == 0 || node > nodes {
+                return Err(format!("line {}: node out of range", number + 1).into());
+            }
+            coordinates[node - 1] = (parse_field(&fields, 2, number)?, parse_field(&fields, 3, number)?);
+        }
+    }
+
+    Ok(coordinates)
+}
+
+fn parse_field<T: std::str::FromStr>(fields: &[&str], index: usize, number: usize) -> GraphResult<T> {
+    fields.get(index)
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| format!("line {}: invalid or missing field {}", number + 1, index + 1).into())
+}
+
+// dijkstra needs finite weights that are never negative
+fn parse_weight(fields: &[&str], index: usize, number: usize) -> GraphResult<f32> {
+    let weight: f32 = parse_field(fields, index, number)?;
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(format!("line {}: weight must be a finite number, at least 0", number + 1).into());
+    }
+    Ok(weight)
+}
+
+// entry of the dijkstra queue, ordered so the closest node pops first
+struct Visit {
+    node: usize,
+    distance: f32
+}
+
+impl PartialEq for Visit {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 - 1 - 2 - 3 with a long shortcut from 0 to 3, and 4 - 5 on their own
+    const EDGES: &str = "from,to,weight\n0,1,1\n1,2,2\n# comment\n2,3,3\n0,3,10\n4,5,1\n";
+
+    #[test]
+    fn edge_list_roads_go_both_ways() {
+        let graph = Graph::from_edge_list(EDGES).unwrap();
+        assert_eq!(graph.node_count(), 6);
+        assert!(graph.coordinates.is_none());
+        assert_eq!(graph.path_length(&[0, 1, 2]), 3.0);
+        assert_eq!(graph.path_length(&[2, 1, 0]), 3.0);
+    }
+
+    #[test]
+    fn dimacs_arcs_go_one_way() {
+        let graph = Graph::from_dimacs("c one way\np sp 3 3\na 1 2 4\na 2 3 5\na 3 1 6\n").unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.shortest_path(0, 2), Some(vec![0, 1, 2]));
+        assert_eq!(graph.shortest_path(2, 1), Some(vec![2, 0, 1]));
+        assert_eq!(graph.path_length(&[0, 1, 2, 0]), 15.0);
+        assert!(graph.path_length(&[1, 0]).is_infinite());
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        assert!(Graph::from_dimacs("a 1 2 3\n").is_err());
+        assert!(Graph::from_dimacs("c no problem line\n").is_err());
+        assert!(Graph::from_dimacs("p sp 2 1\na 1 3 1\n").is_err());
+        assert!(Graph::from_dimacs("p sp 2 1\na 0 1 1\n").is_err());
+        assert!(Graph::from_edge_list("0,1\n").is_err());
+        assert!(Graph::from_edge_list("0,x,1\n").is_err());
+    }
+
+    #[test]
+    fn negative_or_not_finite_weights_are_errors() {
+        for weight in ["-1", "NaN", "inf", "-inf"] {
+            assert!(Graph::from_edge_list(&format!("0,1,{}\n", weight)).is_err());
+            assert!(Graph::from_dimacs(&format!("p sp 2 1\na 1 2 {}\n", weight)).is_err());
+        }
+        assert!(Graph::from_edge_list("0,1,0\n").is_ok());
+    }
+
+    #[test]
+    fn shortest_paths_avoid_the_long_road() {
+        let graph = Graph::from_edge_list(EDGES).unwrap();
+        let (distances, previous) = graph.dijkstra(0, None);
+        assert_eq!(&distances[..4], &[0.0, 1.0, 3.0, 6.0]);
+        assert_eq!(previous[3], Some(2));
+        assert_eq!(graph.shortest_path(0, 3), Some(vec![0, 1, 2, 3]));
+        assert_eq!(graph.shortest_path(3, 3), Some(vec![3]));
+        assert_eq!(graph.metric_closure(&[0, 3, 1]).unwrap(), vec![
+            vec![0.0, 6.0, 1.0],
+            vec![6.0, 0.0, 5.0],
+            vec![1.0, 5.0, 0.0],
+        ]);
+    }
+
+    #[test]
+    fn disconnected_stops_are_errors() {
+        let graph = Graph::from_edge_list(EDGES).unwrap();
+        assert!(graph.dijkstra(0, None).0[4].is_infinite());
+        assert_eq!(graph.shortest_path(0, 5), None);
+        assert!(graph.metric_closure(&[0, 5]).is_err());
+        assert!(graph.metric_closure(&[0, 6]).is_err());
+        assert!(graph.expand_tour(&[0, 4]).is_err());
+    }
+
+    #[test]
+    fn tour_expands_into_road_nodes() {
+        let graph = Graph::from_edge_list(EDGES).unwrap();
+        let path = graph.expand_tour(&[0, 3, 1]).unwrap();
+        assert_eq!(path, vec![0, 1, 2, 3, 2, 1, 0]);
+        assert_eq!(graph.path_length(&path), 12.0);
+        assert_eq!(graph.expand_tour(&[2]).unwrap(), vec![2]);
+    }
+}
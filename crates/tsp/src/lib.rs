@@ -1,17 +1,48 @@
 mod utils;
-mod route;
-mod algorithms;
+pub mod route;
+pub mod algorithms;
+pub mod observer;
+pub mod terminal;
+pub mod precedence;
+pub mod prize;
+pub mod mtsp;
+mod graph;
+pub mod merge;
+pub mod validate;
+pub mod result;
+pub mod checkpoint;
+mod neighbours;
+pub mod partition;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "exact")]
+pub mod exact;
 
 use std::env;
 use std::process;
+use std::sync::Arc;
+use std::time::{ Duration, Instant };
 
+use rand::SeedableRng;
+use rand::seq::index;
+use rand_pcg::Pcg64;
+
+pub use utils::{ Config, Variant, Objective, Strategy };
 use utils::{
-    Config,
     generate_cities,
+    generate_prizes,
     create_plot,
     animate_plot
 };
 use route::Route;
+use algorithms::{ edges_swap, edges_swap_with };
+use prize::PrizeReport;
+use observer::{ SolverEvent, SolverObserver, ProgressLine };
+use terminal::TerminalRenderer;
+use precedence::{ Precedence, precedence_edges_swap };
+use graph::Graph;
+use result::SolveResult;
+use checkpoint::{ Checkpoint, checkpointed_edges_swap };
 
 pub fn travelling_salesman(args: env::Args) {
     let config = Config::new(args).unwrap();
@@ -23,36 +54,368 @@ pub fn travelling_salesman(args: env::Args) {
         process::exit(1);
     }
 
-    let cities = generate_cities(&config);
+    let result = match &config.resume {
+        Some(path) => resume(&config, path),
+        None => solve(&config),
+    };
+
+    if let Some(path) = &config.output {
+        match result::save(&result, path) {
+            Ok(()) => println!("Result written to {}", path),
+            Err(err) => println!("Problem writing the result : {}", err),
+        }
+    }
+}
+
+fn solve(config: &Config) -> SolveResult {
+    println!("Seed : {}", config.seed);
+    let mut rng = Pcg64::seed_from_u64(config.seed);
+
+    match config.variant {
+        Variant::Tsp if config.graph.is_some() => road_network(config, &mut rng),
+        Variant::Tsp if config.pairs > 0 => pickup_delivery(config, &mut rng),
+        Variant::Tsp if config.salesmen > 1 => multiple_salesmen(config, &mut rng),
+        Variant::Tsp if config.exact => exact_tour(config, &mut rng),
+        Variant::Tsp if config.merge > 0 => merged_tour(config, &mut rng),
+        Variant::Tsp if config.partition > 0 => partitioned_tour(config, &mut rng),
+        Variant::Tsp if config.checkpoint.is_some() => checkpointed_tour(Checkpoint::new(config)),
+        Variant::Tsp => shortest_tour(config, &mut rng),
+        Variant::Orienteering => orienteering(config, &mut rng),
+        Variant::PrizeCollecting => prize_collecting(config, &mut rng),
+    }
+}
+
+// carry on with the edge swap saved at `path`
+fn resume(config: &Config, path: &str) -> SolveResult {
+    let mut checkpoint = Checkpoint::load(path).unwrap_or_else(|err| {
+        println!("Problem reading the checkpoint : {}", err);
+        process::exit(1);
+    });
+    // keep saving to the same file unless told otherwise
+    checkpoint.config.checkpoint = Some(config.checkpoint.clone().unwrap_or_else(|| path.to_string()));
+
+    println!("Seed : {}", checkpoint.config.seed);
+    println!("Resuming after pass {}", checkpoint.iteration);
+
+    checkpointed_tour(checkpoint)
+}
+
+fn checkpointed_tour(mut checkpoint: Checkpoint) -> SolveResult {
+    let config = checkpoint.config.clone();
+    let path = config.checkpoint.clone().unwrap();
+    let mut plots = vec![];
+
+    println!("Current Distance : {}", checkpoint.route.total_distance());
+    println!("Calculating...");
+
+    let every = config.checkpoint_every;
+    checkpointed_edges_swap(&mut checkpoint, &path, every, &mut plot_progress(&mut plots, &config))
+        .unwrap_or_else(|err| {
+            println!("Problem writing the checkpoint : {}", err);
+            process::exit(1);
+        });
+    let route = &checkpoint.route;
+
+    println!("Best Distance Results : {}", route.total_distance());
+    println!("Checkpoint written to {}", path);
+
+    if config.saves {
+        animate_plot(&mut plots, &config).unwrap();
+    }
+
+    let runtime = Duration::from_secs_f64(checkpoint.runtime);
+    SolveResult::new(from_depot(&route.indices), route.total_distance(), runtime, "2-opt", config.seed)
+}
+
+fn shortest_tour(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
     let mut route = Route::new(cities);
     let mut plots = vec![];
 
+    match config.strategy {
+        Strategy::Shuffled => println!("Taking the first improving swap of a shuffled pass"),
+        Strategy::First => println!("First improvement with don't-look bits"),
+        Strategy::Best => println!("Best improvement with don't-look bits"),
+        Strategy::Neighbour => println!("Neighbour lists with don't-look bits"),
+    }
     println!("Current Distance : {}", route.total_distance());
     println!("Calculating...");
 
-    // let mut i = 0;
-    loop {
-        // i += 1;
-        let mut swapped = false;
-        let pairings = &route.possible_pairings();
+    let started = Instant::now();
+    let passes = edges_swap_with(&mut route, config.strategy, rng, &mut plot_progress(&mut plots, config));
+    let runtime = started.elapsed();
 
-        for pair in pairings {
-            swapped = route.should_edges_swap(pair.0, pair.1);
+    println!("Passes : {}", passes);
+    println!("Best Distance Results : {}", route.total_distance());
 
-            if swapped {
-                plots.push(create_plot(&route.routes));
-                break;
-            }
-        }
+    if config.saves {
+        animate_plot(&mut plots, config).unwrap();
+    }
 
-        if !swapped {
-            break;
-        }
+    SolveResult::new(from_depot(&route.indices), route.total_distance(), runtime, "2-opt", config.seed)
+}
+
+fn merged_tour(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
+    let route = Route::new(cities);
+    let mut plots = vec![];
+
+    println!("Tour Merging : {} runs of 2-opt", config.merge);
+    println!("Calculating...");
+
+    let started = Instant::now();
+    let (child, parents) = merge::merge_tours(&route, config.merge, rng, &mut plot_progress(&mut plots, config));
+    let runtime = started.elapsed();
+
+    for (i, parent) in parents.iter().enumerate() {
+        println!("Run {} : {}", i + 1, parent.total_distance());
+    }
+    println!("Best Distance Results : {}", child.total_distance());
+
+    if config.saves {
+        animate_plot(&mut plots, config).unwrap();
+    }
+
+    SolveResult::new(from_depot(&child.indices), child.total_distance(), runtime, "partition crossover", config.seed)
+}
+
+fn partitioned_tour(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
+    let route = Route::new(cities);
+
+    println!("Partitioning : cells of at most {} cities", config.partition);
+    println!("Calculating...");
+
+    let started = Instant::now();
+    let report = partition::partitioned_tour(&route, config.partition, rng, display(config).as_mut());
+    let runtime = started.elapsed();
+
+    println!("{}", report);
+
+    let route = &report.route;
+    SolveResult::new(from_depot(&route.indices), route.total_distance(), runtime, "partition", config.seed)
+}
+
+// branch and cut stops here when the instance is too large to finish
+#[cfg(feature = "exact")]
+const EXACT_MAX_NODES: usize = 100_000;
+
+#[cfg(feature = "exact")]
+fn exact_tour(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
+    let route = Route::new(cities);
+    let mut plots = vec![];
+
+    println!("Cutting Plane : {} cities", route.routes.len());
+    println!("Calculating...");
+
+    let started = Instant::now();
+    let report = exact::cutting_plane(&route, EXACT_MAX_NODES, rng, &mut plot_progress(&mut plots, config));
+    let runtime = started.elapsed();
+
+    println!("{}", report);
+
+    if config.saves {
+        animate_plot(&mut plots, config).unwrap();
     }
 
+    let route = &report.route;
+    SolveResult::new(from_depot(&route.indices), route.total_distance(), runtime, "cutting plane", config.seed)
+}
+
+#[cfg(not(feature = "exact"))]
+fn exact_tour(_config: &Config, _rng: &mut Pcg64) -> SolveResult {
+    println!("The exact solver is not built in, run with `--features exact`");
+    process::exit(1);
+}
+
+fn pickup_delivery(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
+    let precedence = Precedence::new(precedence::generate_pairs(cities.len(), config.pairs, rng));
+    let mut plots = vec![];
+
+    println!("Pickup and Delivery : {:?}", precedence.pairs);
+
+    let mut route = precedence.feasible_route(&cities).unwrap_or_else(|err| {
+        println!("No feasible tour : {}", err);
+        process::exit(1);
+    });
+
+    println!("Current Distance : {}", route.total_distance());
+    println!("Calculating...");
+
+    let started = Instant::now();
+    precedence_edges_swap(&mut route, &precedence, rng, &mut plot_progress(&mut plots, config)).unwrap();
+    let runtime = started.elapsed();
+
     println!("Best Distance Results : {}", route.total_distance());
+    match precedence.validate(&route) {
+        Ok(()) => println!("Every pickup comes before its delivery"),
+        Err(err) => println!("Infeasible tour : {}", err),
+    }
 
     if config.saves {
-        animate_plot(&mut plots, &config).unwrap();
+        animate_plot(&mut plots, config).unwrap();
+    }
+
+    SolveResult::new(from_depot(&route.indices), route.total_distance(), runtime, "precedence 2-opt", config.seed)
+}
+
+fn road_network(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let graph = Graph::from_file(config.graph.as_ref().unwrap()).unwrap_or_else(|err| {
+        println!("Problem reading the graph : {}", err);
+        process::exit(1);
+    });
+
+    let stops = match &config.stops {
+        Some(stops) => stops.clone(),
+        None => {
+            let amount = (config.n_city as usize).min(graph.node_count());
+            index::sample(rng, graph.node_count(), amount).into_vec()
+        },
+    };
+    let matrix = graph.metric_closure(&stops).unwrap_or_else(|err| {
+        println!("Problem connecting the stops : {}", err);
+        process::exit(1);
+    });
+    let coordinates = stops.iter()
+        .map(|&stop| graph.coordinates.as_ref().map_or((0, 0), |coordinates| coordinates[stop]))
+        .collect();
+    let mut route = Route::with_matrix(coordinates, (0..stops.len()).collect(), Arc::new(matrix));
+
+    println!("Road Network : {} nodes, {} stops", graph.node_count(), stops.len());
+    println!("Current Distance : {}", route.total_distance());
+    println!("Calculating...");
+
+    let started = Instant::now();
+    edges_swap(&mut route, rng, display(config).as_mut());
+    let runtime = started.elapsed();
+
+    // the swap saw one way arcs averaged out, the tour is reported in its shorter direction
+    // with the length of the path actually driven
+    let mut tour: Vec<usize> = route.indices.iter().map(|&stop| stops[stop]).collect();
+    let mut path = graph.expand_tour(&tour).unwrap();
+    let mut reversed: Vec<usize> = tour.clone();
+    reversed[1..].reverse();
+    let reversed_path = graph.expand_tour(&reversed).unwrap();
+    if graph.path_length(&reversed_path) < graph.path_length(&path) {
+        tour = reversed;
+        path = reversed_path;
+    }
+    let length = graph.path_length(&path);
+
+    println!("Best Distance Results : {}", length);
+    println!("Stops Order : {:?}", tour);
+    println!("Full Path ({} nodes) : {:?}", path.len(), path);
+
+    SolveResult::new(tour, length, runtime, "road network 2-opt", config.seed)
+}
+
+fn multiple_salesmen(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
+    let mut plots = vec![];
+
+    println!("Multiple Travelling Salesmen : {} salesmen", config.salesmen);
+    match config.objective {
+        Objective::MinSum => println!("Minimising total length of all tours"),
+        Objective::MinMax => println!("Minimising length of the longest tour"),
+    }
+    println!("Calculating...");
+
+    let started = Instant::now();
+    let tours = mtsp::multiple_salesmen(
+        &cities,
+        config.salesmen,
+        config.objective,
+        rng,
+        &mut plot_progress(&mut plots, config)
+    ).unwrap_or_else(|err| {
+        println!("Problem splitting the cities : {}", err);
+        process::exit(1);
+    });
+    let runtime = started.elapsed();
+
+    for (i, tour) in tours.iter().enumerate() {
+        println!("Salesman {} : {:?} ({})", i + 1, tour.indices, tour.total_distance());
+    }
+    println!("Total Distance : {}", mtsp::total_distance(&tours));
+    println!("Longest Tour : {}", mtsp::longest_tour(&tours));
+
+    if config.saves {
+        animate_plot(&mut plots, config).unwrap();
+    }
+
+    let tour = tours.iter().flat_map(|tour| from_depot(&tour.indices)).collect();
+    SolveResult::new(tour, mtsp::total_distance(&tours), runtime, "multiple salesmen", config.seed)
+}
+
+fn orienteering(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
+    let prizes = generate_prizes(config, 100, rng);
+    let budget = config.budget.unwrap_or(config.border as f32 * 2.0);
+
+    println!("Orienteering with length budget : {}", budget);
+    println!("Calculating...");
+
+    let mut plots = vec![];
+    let started = Instant::now();
+    let report = prize::orienteering(&cities, &prizes, budget, rng, &mut plot_progress(&mut plots, config));
+    let runtime = started.elapsed();
+    print_report(&report, &mut plots, config);
+
+    SolveResult::new(from_depot(&report.route.indices), report.distance, runtime, "orienteering", config.seed)
+}
+
+fn prize_collecting(config: &Config, rng: &mut Pcg64) -> SolveResult {
+    let cities = generate_cities(config, rng);
+    let penalties = generate_prizes(config, config.border / 4, rng);
+
+    println!("Prize-Collecting TSP");
+    println!("Calculating...");
+
+    let mut plots = vec![];
+    let started = Instant::now();
+    let report = prize::prize_collecting(&cities, &penalties, rng, &mut plot_progress(&mut plots, config));
+    let runtime = started.elapsed();
+    println!("Objective (distance + penalty) : {}", report.distance + report.penalty as f32);
+    print_report(&report, &mut plots, config);
+
+    SolveResult::new(from_depot(&report.route.indices), report.distance, runtime, "prize collecting", config.seed)
+}
+
+fn print_report(report: &PrizeReport, plots: &mut Vec<Vec<Vec<(f64, f64)>>>, config: &Config) {
+    println!("{}", report);
+
+    if config.saves {
+        animate_plot(plots, config).unwrap();
+    }
+}
+
+// city indices of a closed tour, rotated to start at the depot
+fn from_depot(indices: &[usize]) -> Vec<usize> {
+    let start = indices.iter().position(|&city| city == 0).unwrap_or(0);
+    indices[start..].iter().chain(&indices[..start]).cloned().collect()
+}
+
+// either a progress line or the tours drawn on the terminal
+fn display(config: &Config) -> Box<dyn SolverObserver> {
+    match config.terminal {
+        true => Box::new(TerminalRenderer::fit_terminal()),
+        false => Box::new(ProgressLine::new()),
+    }
+}
+
+// display the progress while keeping a plot of every improvement for the gif
+fn plot_progress<'a>(
+    plots: &'a mut Vec<Vec<Vec<(f64, f64)>>>,
+    config: &Config
+) -> impl FnMut(&SolverEvent) + 'a {
+    let mut display = display(config);
+
+    move |event| {
+        display.notify(event);
+        if let SolverEvent::Improvement { tours, .. } = event {
+            plots.push(tours.iter().map(|tour| create_plot(&tour.routes)).collect());
+        }
     }
 }
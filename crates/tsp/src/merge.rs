@@ -0,0 +1,200 @@
+use std::collections::{ HashMap, HashSet };
+use std::slice;
+
+use rand::RngCore;
+use rand::seq::SliceRandom;
+
+use crate::algorithms::edges_swap;
+use crate::observer::{ Silent, SolverEvent, SolverObserver };
+use crate::route::Route;
+
+// the two cities it connects, the lower one first
+type Edge = (usize, usize);
+
+// run 2-opt from `starts` shuffled orders of `route` and merge the local optima
+pub fn merge_tours(
+    route: &Route,
+    starts: usize,
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> (Route, Vec<Route>) {
+    let mut parents = vec![];
+
+    for restart in 1..=starts {
+        let mut order: Vec<usize> = (0..route.routes.len()).collect();
+        order.shuffle(rng);
+        let mut parent = route.reordered(&order);
+        edges_swap(&mut parent, rng, &mut Silent);
+
+        observer.notify(&SolverEvent::Restart { restart, distance: parent.total_distance() });
+        parents.push(parent);
+    }
+
+    (partition_crossover(&parents, observer), parents)
+}
+
+// generalized partition crossover, starting from the shortest parent every other one
+// lends the pieces of its tour that are shorter, so the child is never longer than the best parent;
+// parents that do not visit the same cities as the best one are left out
+pub fn partition_crossover(parents: &[Route], observer: &mut dyn SolverObserver) -> Route {
+    let mut child = parents.iter()
+        .min_by(|a, b| a.total_distance().partial_cmp(&b.total_distance()).unwrap())
+        .expect("partition crossover needs at least one parent")
+        .clone();
+
+    // a piece that did not fit before can fit the child once other pieces changed
+    loop {
+        let mut improved = false;
+        for parent in parents {
+            if let Some(better) = crossover(&child, parent) {
+                child = better;
+                improved = true;
+                observer.notify(&SolverEvent::Improvement {
+                    tours: slice::from_ref(&child),
+                    distance: child.total_distance()
+                });
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    observer.notify(&SolverEvent::Finished { tours: slice::from_ref(&child), distance: child.total_distance() });
+    child
+}
+
+// `base` with every piece of `other` that shortens it and keeps a single tour,
+// none when nothing of `other` helps
+fn crossover(base: &Route, other: &Route) -> Option<Route> {
+    let length = base.indices.len();
+    let positions = positions(&base.indices)?;
+    if other.indices.len() != length || other.indices.iter().any(|&city| positions.get(city).is_none_or(Option::is_none)) {
+        return None;
+    }
+    // cities are numbered by their position in `base` from here on
+    let other_order: Vec<usize> = other.indices.iter().map(|&city| positions[city].unwrap()).collect();
+
+    let base_edges = tour_edges(&(0..length).collect::<Vec<usize>>());
+    let other_edges = tour_edges(&other_order);
+    let in_base: HashSet<Edge> = base_edges.iter().cloned().collect();
+    let in_other: HashSet<Edge> = other_edges.iter().cloned().collect();
+
+    // the union graph without its shared edges falls apart into the pieces the parents disagree on
+    let mut pieces = UnionFind::new(length);
+    for &(a, b) in in_base.symmetric_difference(&in_other) {
+        pieces.union(a, b);
+    }
+    let mut differences: HashMap<usize, (Vec<Edge>, Vec<Edge>)> = HashMap::new();
+    for &edge in base_edges.iter().filter(|edge| !in_other.contains(edge)) {
+        differences.entry(pieces.find(edge.0)).or_default().0.push(edge);
+    }
+    for &edge in other_edges.iter().filter(|edge| !in_base.contains(edge)) {
+        differences.entry(pieces.find(edge.0)).or_default().1.push(edge);
+    }
+
+    let cost = |edges: &[Edge]| -> f32 { edges.iter().map(|&(a, b)| base.distance(a, b)).sum() };
+    let mut candidates: Vec<(f32, Vec<Edge>, Vec<Edge>)> = differences.into_values()
+        .map(|(removed, added)| (cost(&removed) - cost(&added), removed, added))
+        .filter(|&(gain, _, _)| gain > 1e-3)
+        .collect();
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    // a piece is only taken over when the result is still a single tour,
+    // which also rules out pieces the parents enter through more than two shared edges
+    let mut edges = in_base;
+    let mut improved = false;
+    for (_, removed, added) in candidates {
+        let mut swapped = edges.clone();
+        for edge in &removed {
+            swapped.remove(edge);
+        }
+        swapped.extend(added);
+        if single_tour(&swapped, length).is_some() {
+            edges = swapped;
+            improved = true;
+        }
+    }
+
+    if !improved {
+        return None;
+    }
+    let child = base.reordered(&single_tour(&edges, length)?);
+    match child.total_distance() < base.total_distance() {
+        true => Some(child),
+        false => None,
+    }
+}
+
+// edges of a closed tour
+fn tour_edges(order: &[usize]) -> Vec<Edge> {
+    (0..order.len())
+        .map(|i| {
+            let (a, b) = (order[i], order[(i + 1) % order.len()]);
+            (a.min(b), a.max(b))
+        })
+        .collect()
+}
+
+// visiting order when `edges` make one closed tour through every city
+fn single_tour(edges: &HashSet<Edge>, length: usize) -> Option<Vec<usize>> {
+    let mut neighbours = vec![vec![]; length];
+    for &(a, b) in edges {
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+    }
+    if neighbours.iter().any(|next| next.len() != 2) {
+        return None;
+    }
+
+    let mut order = vec![0];
+    let (mut prev, mut city) = (0, neighbours[0][0]);
+    while city != 0 {
+        order.push(city);
+        let next = if neighbours[city][0] != prev { neighbours[city][0] } else { neighbours[city][1] };
+        prev = city;
+        city = next;
+    }
+
+    match order.len() == length {
+        true => Some(order),
+        false => None,
+    }
+}
+
+// position of every city in `indices`, none if a city shows up twice
+fn positions(indices: &[usize]) -> Option<Vec<Option<usize>>> {
+    let mut positions = vec![None; indices.iter().max().map_or(0, |max| max + 1)];
+    for (position, &city) in indices.iter().enumerate() {
+        if positions[city].replace(position).is_some() {
+            return None;
+        }
+    }
+
+    Some(positions)
+}
+
+struct UnionFind {
+    parent: Vec<usize>
+}
+
+impl UnionFind {
+    fn new(length: usize) -> UnionFind {
+        UnionFind { parent: (0..length).collect() }
+    }
+
+    fn find(&mut self, city: usize) -> usize {
+        let mut root = city;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        self.parent[city] = root;
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+}
@@ -0,0 +1,253 @@
+use rand::RngCore;
+
+use crate::algorithms::edges_swap;
+use crate::route::Route;
+use crate::observer::{ SolverEvent, SolverObserver, Silent };
+use crate::utils::{ Objective, insertion_cost, removal_saving };
+
+// every salesman starts and ends at the first city
+const DEPOT: usize = 0;
+// every accepted move shortens the tours, this only bounds a very long search
+const MAX_ITERATIONS: usize = 10_000;
+
+// split the cities among `salesmen` tours that all share the depot,
+// reported distances are the value of `objective`,
+// every salesman visits at least one city besides the depot
+pub fn multiple_salesmen(
+    cities: &[(i32, i32)],
+    salesmen: usize,
+    objective: Objective,
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> Result<Vec<Route>, &'static str> {
+    if salesmen == 0 {
+        return Err("salesmen must be a positive integer");
+    }
+    if salesmen >= cities.len() {
+        return Err("salesmen must be fewer than the cities");
+    }
+
+    let mut tours = sweep_tours(cities, salesmen);
+    let mut iteration = 0;
+    for tour in tours.iter_mut() {
+        edges_swap(tour, rng, &mut Silent);
+    }
+    observer.notify(&SolverEvent::Improvement {
+        tours: &tours,
+        distance: score(&lengths(&tours), objective).0
+    });
+
+    loop {
+        iteration += 1;
+        observer.notify(&SolverEvent::Iteration {
+            iteration,
+            distance: score(&lengths(&tours), objective).0
+        });
+
+        let improved = relocate(&mut tours, cities, objective, rng)
+            || exchange(&mut tours, cities, objective, rng);
+
+        if !improved {
+            break;
+        }
+        observer.notify(&SolverEvent::Improvement {
+            tours: &tours,
+            distance: score(&lengths(&tours), objective).0
+        });
+        if iteration >= MAX_ITERATIONS {
+            break;
+        }
+    }
+
+    observer.notify(&SolverEvent::Finished {
+        tours: &tours,
+        distance: score(&lengths(&tours), objective).0
+    });
+    Ok(tours)
+}
+
+pub fn longest_tour(tours: &[Route]) -> f32 {
+    tours.iter().map(|tour| tour.total_distance()).fold(0.0, f32::max)
+}
+
+pub fn total_distance(tours: &[Route]) -> f32 {
+    tours.iter().map(|tour| tour.total_distance()).sum()
+}
+
+// the value being minimised first, then the other one as a tie breaker
+fn score(lengths: &[f32], objective: Objective) -> (f32, f32) {
+    let sum = lengths.iter().sum();
+    let max = lengths.iter().cloned().fold(0.0, f32::max);
+
+    match objective {
+        Objective::MinSum => (sum, max),
+        Objective::MinMax => (max, sum),
+    }
+}
+
+fn is_better(candidate: (f32, f32), current: (f32, f32)) -> bool {
+    let epsilon = 1e-3;
+    if (candidate.0 - current.0).abs() <= epsilon {
+        candidate.1 < current.1 - epsilon
+    } else {
+        candidate.0 < current.0
+    }
+}
+
+// give each salesman a slice of the cities ordered by their angle around the depot,
+// the slices differ by one city at most
+fn sweep_tours(cities: &[(i32, i32)], salesmen: usize) -> Vec<Route> {
+    let depot = cities[DEPOT];
+    let mut others: Vec<usize> = (0..cities.len()).filter(|&city| city != DEPOT).collect();
+    others.sort_by(|&a, &b| {
+        let angle_a = ((cities[a].1 - depot.1) as f32).atan2((cities[a].0 - depot.0) as f32);
+        let angle_b = ((cities[b].1 - depot.1) as f32).atan2((cities[b].0 - depot.0) as f32);
+        angle_a.partial_cmp(&angle_b).unwrap()
+    });
+
+    (0..salesmen)
+        .map(|salesman| {
+            let group = &others[salesman * others.len() / salesmen..(salesman + 1) * others.len() / salesmen];
+            let mut indices = vec![DEPOT];
+            indices.extend_from_slice(group);
+            build_tour(cities, indices)
+        })
+        .collect()
+}
+
+fn build_tour(cities: &[(i32, i32)], indices: Vec<usize>) -> Route {
+    let routes = indices.iter().map(|&city| cities[city]).collect();
+    Route::with_indices(routes, indices)
+}
+
+fn lengths(tours: &[Route]) -> Vec<f32> {
+    tours.iter().map(|tour| tour.total_distance()).collect()
+}
+
+// rebuild and re-optimise the two changed tours, the estimated score of a move
+// can miss the randomised edge swap, so the move is undone unless the real score improves
+fn apply(
+    tours: &mut [Route],
+    cities: &[(i32, i32)],
+    changed: [(usize, Vec<usize>); 2],
+    objective: Objective,
+    rng: &mut dyn RngCore
+) -> bool {
+    let current = score(&lengths(tours), objective);
+    let previous: Vec<Route> = changed.iter().map(|(tour, _)| tours[*tour].clone()).collect();
+
+    for (tour, indices) in changed.iter() {
+        tours[*tour] = build_tour(cities, indices.clone());
+        edges_swap(&mut tours[*tour], rng, &mut Silent);
+    }
+
+    if is_better(score(&lengths(tours), objective), current) {
+        return true;
+    }
+    for ((tour, _), route) in changed.iter().zip(previous) {
+        tours[*tour] = route;
+    }
+    false
+}
+
+// inter-tour move: take one city out of a tour and put it into another one
+fn relocate(
+    tours: &mut [Route],
+    cities: &[(i32, i32)],
+    objective: Objective,
+    rng: &mut dyn RngCore
+) -> bool {
+    let current_lengths = lengths(tours);
+    let current = score(&current_lengths, objective);
+
+    for from in 0..tours.len() {
+        // keep at least one city besides the depot in every tour
+        if tours[from].routes.len() <= 2 {
+            continue;
+        }
+
+        for position in 0..tours[from].routes.len() {
+            let city = tours[from].indices[position];
+            if city == DEPOT {
+                continue;
+            }
+            let saving = removal_saving(&tours[from].routes, position);
+
+            for to in (0..tours.len()).filter(|&to| to != from) {
+                let (insert_at, cost) = insertion_cost(&tours[to].routes, &cities[city]);
+                let mut candidate = current_lengths.clone();
+                candidate[from] -= saving;
+                candidate[to] += cost;
+
+                if is_better(score(&candidate, objective), current) {
+                    let mut from_indices = tours[from].indices.clone();
+                    from_indices.remove(position);
+                    let mut to_indices = tours[to].indices.clone();
+                    to_indices.insert(insert_at, city);
+
+                    if apply(tours, cities, [(from, from_indices), (to, to_indices)], objective, rng) {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+
+    false
+}
+
+// inter-tour move: swap one city of a tour with one city of another tour
+fn exchange(
+    tours: &mut [Route],
+    cities: &[(i32, i32)],
+    objective: Objective,
+    rng: &mut dyn RngCore
+) -> bool {
+    let current_lengths = lengths(tours);
+    let current = score(&current_lengths, objective);
+
+    for a in 0..tours.len() {
+        for b in a + 1..tours.len() {
+            for position_a in 0..tours[a].routes.len() {
+                let city_a = tours[a].indices[position_a];
+                if city_a == DEPOT {
+                    continue;
+                }
+                let mut without_a = tours[a].routes.clone();
+                without_a.remove(position_a);
+                let saving_a = removal_saving(&tours[a].routes, position_a);
+
+                for position_b in 0..tours[b].routes.len() {
+                    let city_b = tours[b].indices[position_b];
+                    if city_b == DEPOT {
+                        continue;
+                    }
+                    let mut without_b = tours[b].routes.clone();
+                    without_b.remove(position_b);
+                    let saving_b = removal_saving(&tours[b].routes, position_b);
+
+                    let (insert_b, cost_b) = insertion_cost(&without_a, &cities[city_b]);
+                    let (insert_a, cost_a) = insertion_cost(&without_b, &cities[city_a]);
+                    let mut candidate = current_lengths.clone();
+                    candidate[a] += cost_b - saving_a;
+                    candidate[b] += cost_a - saving_b;
+
+                    if is_better(score(&candidate, objective), current) {
+                        let mut indices_a = tours[a].indices.clone();
+                        indices_a.remove(position_a);
+                        indices_a.insert(insert_b, city_b);
+                        let mut indices_b = tours[b].indices.clone();
+                        indices_b.remove(position_b);
+                        indices_b.insert(insert_a, city_a);
+
+                        if apply(tours, cities, [(a, indices_a), (b, indices_b)], objective, rng) {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    false
+}
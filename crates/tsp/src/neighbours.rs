@@ -0,0 +1,91 @@
+use rayon::prelude::*;
+
+// about this many cities share a cell of the grid
+const CITIES_PER_CELL: usize = 2;
+
+// the `k` closest other cities of every city, closest first,
+// found through a uniform grid instead of comparing every pair
+pub fn nearest_neighbours(cities: &[(i32, i32)], k: usize) -> Vec<Vec<usize>> {
+    let grid = Grid::new(cities);
+    (0..cities.len())
+        .into_par_iter()
+        .map(|city| grid.nearest(cities, city, k))
+        .collect()
+}
+
+struct Grid {
+    min: (i64, i64),
+    cell: i64,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>
+}
+
+impl Grid {
+    fn new(cities: &[(i32, i32)]) -> Grid {
+        let min_x = cities.iter().map(|city| city.0 as i64).min().unwrap_or(0);
+        let min_y = cities.iter().map(|city| city.1 as i64).min().unwrap_or(0);
+        let max_x = cities.iter().map(|city| city.0 as i64).max().unwrap_or(0);
+        let max_y = cities.iter().map(|city| city.1 as i64).max().unwrap_or(0);
+        let span = (max_x - min_x).max(max_y - min_y) + 1;
+        let side = ((cities.len() / CITIES_PER_CELL) as f64).sqrt().ceil().max(1.0) as i64;
+        let cell = (span + side - 1) / side;
+        let columns = ((max_x - min_x) / cell + 1) as usize;
+        let rows = ((max_y - min_y) / cell + 1) as usize;
+
+        let mut grid = Grid { min: (min_x, min_y), cell, columns, rows, cells: vec![vec![]; columns * rows] };
+        for (city, &coordinates) in cities.iter().enumerate() {
+            let (column, row) = grid.locate(coordinates);
+            grid.cells[row * columns + column].push(city);
+        }
+
+        grid
+    }
+
+    fn locate(&self, city: (i32, i32)) -> (usize, usize) {
+        (((city.0 as i64 - self.min.0) / self.cell) as usize, ((city.1 as i64 - self.min.1) / self.cell) as usize)
+    }
+
+    // search rings of cells around the city until no unvisited cell can hold anything closer
+    fn nearest(&self, cities: &[(i32, i32)], city: usize, k: usize) -> Vec<usize> {
+        if k == 0 {
+            return vec![];
+        }
+        let (column, row) = self.locate(cities[city]);
+        let squared = |other: usize| {
+            let (dx, dy) = (cities[other].0 as i64 - cities[city].0 as i64, cities[other].1 as i64 - cities[city].1 as i64);
+            dx * dx + dy * dy
+        };
+        let mut found: Vec<(i64, usize)> = vec![];
+
+        for ring in 0..self.columns.max(self.rows) {
+            let ring = ring as i64;
+            for y in row as i64 - ring..=row as i64 + ring {
+                for x in column as i64 - ring..=column as i64 + ring {
+                    let on_ring = (y - row as i64).abs() == ring || (x - column as i64).abs() == ring;
+                    if !on_ring || x < 0 || y < 0 || x >= self.columns as i64 || y >= self.rows as i64 {
+                        continue;
+                    }
+                    for &other in &self.cells[y as usize * self.columns + x as usize] {
+                        if other != city {
+                            found.push((squared(other), other));
+                        }
+                    }
+                }
+            }
+
+            // anything in the next ring is at least `ring` cells away
+            if found.len() >= k {
+                found.sort_unstable();
+                found.truncate(k);
+                let reach = ring * self.cell;
+                if found[k - 1].0 <= reach * reach {
+                    break;
+                }
+            }
+        }
+        found.sort_unstable();
+
+        found.into_iter().take(k).map(|(_, other)| other).collect()
+    }
+}
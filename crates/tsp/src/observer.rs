@@ -0,0 +1,108 @@
+use std::io::{ self, Write };
+use std::time::{ Duration, Instant };
+
+use crate::route::Route;
+
+// what a solver reports while it runs, `tours` holds a single route
+// except for solvers with several salesmen
+pub enum SolverEvent<'a> {
+    // a new pass over the candidate moves started
+    Iteration { iteration: usize, distance: f32 },
+    // a move made the solution better
+    Improvement { tours: &'a [Route], distance: f32 },
+    // the search continues from a different solution
+    Restart { restart: usize, distance: f32 },
+    // no more improvements, `tours` is the final solution
+    Finished { tours: &'a [Route], distance: f32 }
+}
+
+pub trait SolverObserver {
+    fn notify(&mut self, event: &SolverEvent);
+}
+
+impl<F: FnMut(&SolverEvent)> SolverObserver for F {
+    fn notify(&mut self, event: &SolverEvent) {
+        self(event)
+    }
+}
+
+// ignores every event, for solvers running inside another solver
+pub struct Silent;
+
+impl SolverObserver for Silent {
+    fn notify(&mut self, _event: &SolverEvent) {}
+}
+
+// single terminal line with the current distance and how fast it improves
+pub struct ProgressLine {
+    started: Instant,
+    last_print: Option<Instant>,
+    initial: Option<f32>,
+    iterations: usize,
+    improvements: usize
+}
+
+impl ProgressLine {
+    pub fn new() -> ProgressLine {
+        ProgressLine {
+            started: Instant::now(),
+            last_print: None,
+            initial: None,
+            iterations: 0,
+            improvements: 0
+        }
+    }
+
+    fn print(&mut self, distance: f32) {
+        let elapsed = self.started.elapsed().as_secs_f32().max(1e-3);
+        let initial = *self.initial.get_or_insert(distance);
+
+        print!(
+            "\r{:>8.1}s | Distance : {:.3} | Iterations : {} | Improvements : {} ({:.1}/s, -{:.3}/s)   ",
+            elapsed,
+            distance,
+            self.iterations,
+            self.improvements,
+            self.improvements as f32 / elapsed,
+            (initial - distance).max(0.0) / elapsed
+        );
+        io::stdout().flush().unwrap_or(());
+        self.last_print = Some(Instant::now());
+    }
+
+    // redrawing the line on every event slows the solver down for nothing
+    fn should_print(&self) -> bool {
+        self.last_print.is_none_or(|last| last.elapsed() >= Duration::from_millis(100))
+    }
+}
+
+impl Default for ProgressLine {
+    fn default() -> Self {
+        ProgressLine::new()
+    }
+}
+
+impl SolverObserver for ProgressLine {
+    fn notify(&mut self, event: &SolverEvent) {
+        match *event {
+            SolverEvent::Iteration { distance, .. } => {
+                self.iterations += 1;
+                self.initial.get_or_insert(distance);
+                if self.should_print() {
+                    self.print(distance);
+                }
+            },
+            SolverEvent::Improvement { distance, .. } => {
+                self.improvements += 1;
+                if self.should_print() {
+                    self.print(distance);
+                }
+            },
+            SolverEvent::Restart { .. } => {},
+            SolverEvent::Finished { distance, .. } => {
+                self.print(distance);
+                println!();
+            },
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::slice;
+
+use rand::{ RngCore, SeedableRng };
+use rand_pcg::Pcg64;
+
+use crate::algorithms::edges_swap;
+use crate::neighbours::nearest_neighbours;
+use crate::observer::{ Silent, SolverEvent, SolverObserver };
+use crate::route::Route;
+
+// edges of each half closest to the split line tried when joining the two halves
+const STITCH_CANDIDATES: usize = 64;
+// closest cities the boundary improvement tries to connect to
+const NEIGHBOURS: usize = 8;
+// longest stretch of the tour a 2-opt move of the boundary improvement reverses
+const MAX_REVERSAL: usize = 1_000;
+// longest run of stops or-opt moves elsewhere
+const SEGMENT: usize = 3;
+// gains smaller than this are float noise
+const EPSILON: f64 = 1e-7;
+
+pub struct PartitionReport {
+    pub route: Route,
+    pub cells: usize,
+    // length once the cell tours are stitched together, before the boundary improvement
+    pub stitched: f32,
+    // cities with a close neighbour in another cell
+    pub boundary: usize,
+    pub moves: usize
+}
+
+impl fmt::Display for PartitionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Cells : {}", self.cells)?;
+        writeln!(f, "Stitched Distance : {}", self.stitched)?;
+        writeln!(f, "Boundary Cities : {}", self.boundary)?;
+        writeln!(f, "Boundary Moves : {}", self.moves)?;
+        write!(f, "Distance : {}", self.route.total_distance())
+    }
+}
+
+// coordinates of a city and its position in the route being partitioned,
+// carried together so the recursion never looks far away in memory
+type Stop = ((i32, i32), usize);
+
+// Karp style divide and conquer for very large instances,
+// the plane is halved at the median until at most `cell_size` cities are left in a cell,
+// every cell gets its own 2-opt tour in parallel and sibling tours are joined bottom up,
+// then 2-opt and or-opt moves clean up around the cities next to another cell
+pub fn partitioned_tour(
+    route: &Route,
+    cell_size: usize,
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> PartitionReport {
+    let length = route.routes.len();
+    let mut stops: Vec<Stop> = route.routes.iter().cloned().zip(0..).collect();
+    let mut cells = vec![0; length];
+
+    let tour = solve_cell(&mut stops, &mut cells, 0, cell_size.max(1), rng.next_u64());
+    let mut cell_of = vec![0; length];
+    for (stop, &cell) in stops.iter().zip(&cells) {
+        cell_of[stop.1] = cell;
+    }
+
+    // cities are numbered along the stitched tour from here on, neighbours on the tour stay close in memory
+    let cities: Vec<(i32, i32)> = tour.iter().map(|stop| stop.0).collect();
+    let cell_of: Vec<usize> = tour.iter().map(|stop| cell_of[stop.1]).collect();
+    let stitched = closed_length(&cities) as f32;
+    observer.notify(&SolverEvent::Iteration { iteration: 1, distance: stitched });
+
+    let neighbours = nearest_neighbours(&cities, NEIGHBOURS.min(length.saturating_sub(1)));
+    let boundary: Vec<usize> = (0..length)
+        .filter(|&city| neighbours[city].iter().any(|&other| cell_of[other] != cell_of[city]))
+        .collect();
+
+    let mut linked = LinkedTour::new(&cities);
+    let moves = linked.improve(&boundary, &neighbours);
+    let order: Vec<usize> = linked.order().iter().map(|&city| tour[city].1).collect();
+    let improved = route.reordered(&order);
+
+    observer.notify(&SolverEvent::Finished {
+        tours: slice::from_ref(&improved),
+        distance: improved.total_distance()
+    });
+
+    PartitionReport {
+        route: improved,
+        cells: cells.iter().zip(cells.iter().skip(1)).filter(|(a, b)| a != b).count() + 1,
+        stitched,
+        boundary: boundary.len(),
+        moves
+    }
+}
+
+// closed tour through `stops`, split at the median along the longer side of its bounding box;
+// `cells` gets the first position of the cell every one of `stops` ends up in
+fn solve_cell(stops: &mut [Stop], cells: &mut [usize], offset: usize, cell_size: usize, seed: u64) -> Vec<Stop> {
+    if stops.len() <= cell_size {
+        cells.iter_mut().for_each(|cell| *cell = offset);
+        return solve_leaf(stops, seed);
+    }
+
+    let (min_x, max_x) = bounds(stops.iter().map(|stop| stop.0.0));
+    let (min_y, max_y) = bounds(stops.iter().map(|stop| stop.0.1));
+    let horizontal = max_x - min_x >= max_y - min_y;
+    let axis = |stop: &Stop| if horizontal { stop.0.0 } else { stop.0.1 };
+
+    let middle = stops.len() / 2;
+    stops.select_nth_unstable_by_key(middle, axis);
+    let split = axis(&stops[middle]) as f64;
+
+    let mut rng = Pcg64::seed_from_u64(seed);
+    let seeds = (rng.next_u64(), rng.next_u64());
+    let (left_stops, right_stops) = stops.split_at_mut(middle);
+    let (left_cells, right_cells) = cells.split_at_mut(middle);
+    let (left, right) = rayon::join(
+        || solve_cell(left_stops, left_cells, offset, cell_size, seeds.0),
+        || solve_cell(right_stops, right_cells, offset + middle, cell_size, seeds.1)
+    );
+
+    stitch(&left, &right, |stop| (axis(stop) as f64 - split).abs())
+}
+
+// the cities of a single cell ordered by the 2-opt edge swap
+fn solve_leaf(stops: &[Stop], seed: u64) -> Vec<Stop> {
+    if stops.len() < 4 {
+        return stops.to_vec();
+    }
+
+    let mut route = Route::new(stops.iter().map(|stop| stop.0).collect());
+    edges_swap(&mut route, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+    route.indices.iter().map(|&position| stops[position]).collect()
+}
+
+// one closed tour out of two, by dropping an edge of each and connecting their ends across,
+// only the edges closest to the split line between them are tried
+fn stitch(left: &[Stop], right: &[Stop], distance_to_split: impl Fn(&Stop) -> f64) -> Vec<Stop> {
+    let edge = |tour: &[Stop], i: usize| (tour[i].0, tour[(i + 1) % tour.len()].0);
+    let candidates = |tour: &[Stop]| -> Vec<usize> {
+        let mut edges: Vec<(f64, usize)> = (0..tour.len())
+            .map(|i| (distance_to_split(&tour[i]).max(distance_to_split(&tour[(i + 1) % tour.len()])), i))
+            .collect();
+        if edges.len() > STITCH_CANDIDATES {
+            edges.select_nth_unstable_by(STITCH_CANDIDATES, |a, b| a.0.partial_cmp(&b.0).unwrap());
+            edges.truncate(STITCH_CANDIDATES);
+        }
+        edges.into_iter().map(|(_, i)| i).collect()
+    };
+
+    // (cost, left edge, right edge, crossed) where crossed connects the first ends with each other
+    let mut best = (f64::MAX, 0, 0, false);
+    for i in candidates(left) {
+        let (a1, a2) = edge(left, i);
+        for j in candidates(right) {
+            let (b1, b2) = edge(right, j);
+            let removed = distance(a1, a2) + distance(b1, b2);
+            let crossed = distance(a1, b1) + distance(a2, b2) - removed;
+            let straight = distance(a1, b2) + distance(a2, b1) - removed;
+            if crossed < best.0 {
+                best = (crossed, i, j, true);
+            }
+            if straight < best.0 {
+                best = (straight, i, j, false);
+            }
+        }
+    }
+
+    // the left tour from the end of its dropped edge round to its start, then across
+    let (_, i, j, crossed) = best;
+    let mut tour = Vec::with_capacity(left.len() + right.len());
+    tour.extend(left[i + 1..].iter().chain(&left[..=i]));
+    match crossed {
+        // walk the right tour backwards from the start of its dropped edge
+        true => tour.extend(right[..=j].iter().rev().chain(right[j + 1..].iter().rev())),
+        false => tour.extend(right[j + 1..].iter().chain(&right[..=j])),
+    }
+
+    tour
+}
+
+fn bounds(values: impl Iterator<Item = i32>) -> (i64, i64) {
+    values.fold((i64::MAX, i64::MIN), |(min, max), value| (min.min(value as i64), max.max(value as i64)))
+}
+
+// the sums over a million cities need more than f32
+fn distance(a: (i32, i32), b: (i32, i32)) -> f64 {
+    let (dx, dy) = (a.0 as f64 - b.0 as f64, a.1 as f64 - b.1 as f64);
+    (dx * dx + dy * dy).sqrt()
+}
+
+fn closed_length(cities: &[(i32, i32)]) -> f64 {
+    cities.iter()
+        .zip(cities.iter().cycle().skip(1))
+        .map(|(&a, &b)| distance(a, b))
+        .sum()
+}
+
+// a tour as the next and previous city of every city, or-opt moves stay constant time
+// however far apart the cities are on the tour
+struct LinkedTour<'a> {
+    cities: &'a [(i32, i32)],
+    next: Vec<usize>,
+    prev: Vec<usize>
+}
+
+impl<'a> LinkedTour<'a> {
+    // the cities visited in the order they are numbered
+    fn new(cities: &'a [(i32, i32)]) -> LinkedTour<'a> {
+        let length = cities.len();
+        let next = (0..length).map(|city| (city + 1) % length).collect();
+        let prev = (0..length).map(|city| (city + length - 1) % length).collect();
+
+        LinkedTour { cities, next, prev }
+    }
+
+    fn order(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.next.len());
+        let mut city = 0;
+        for _ in 0..self.next.len() {
+            order.push(city);
+            city = self.next[city];
+        }
+
+        order
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f64 {
+        distance(self.cities[a], self.cities[b])
+    }
+
+    // try to improve around every city of `start`, a city whose edges change gets looked at again,
+    // returns how many moves were made
+    fn improve(&mut self, start: &[usize], neighbours: &[Vec<usize>]) -> usize {
+        if self.next.len() < 8 {
+            return 0;
+        }
+
+        let mut queued = vec![false; self.next.len()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        for &city in start {
+            queued[city] = true;
+            queue.push_back(city);
+        }
+
+        let mut moves = 0;
+        while let Some(city) = queue.pop_front() {
+            queued[city] = false;
+            let touched = match self.two_opt(city, &neighbours[city]) {
+                Some(touched) => touched,
+                None => match self.or_opt(city, &neighbours[city]) {
+                    Some(touched) => touched,
+                    None => continue,
+                },
+            };
+
+            moves += 1;
+            for touched in touched.into_iter().chain([city]) {
+                if !queued[touched] {
+                    queued[touched] = true;
+                    queue.push_back(touched);
+                }
+            }
+        }
+
+        moves
+    }
+
+    // replace an edge of `city` and an edge of one of its neighbours by the edge between them,
+    // returns the cities whose edges changed
+    fn two_opt(&mut self, city: usize, neighbours: &[usize]) -> Option<Vec<usize>> {
+        for &other in neighbours {
+            // dropping the edges after both cities, then after both their predecessors
+            for (a, b) in [(city, other), (self.prev[city], self.prev[other])] {
+                let (next_a, next_b) = (self.next[a], self.next[b]);
+                if a == b || next_a == b || next_b == a {
+                    continue;
+                }
+                let gain = self.distance(a, next_a) + self.distance(b, next_b)
+                    - self.distance(a, b) - self.distance(next_a, next_b);
+                if gain > EPSILON && self.exchange(a, b) {
+                    return Some(vec![a, b, next_a, next_b]);
+                }
+            }
+        }
+
+        None
+    }
+
+    // drop the edges after `a` and after `b` and connect `a` with `b` and their old successors,
+    // the shorter way round gets reversed unless both are longer than `MAX_REVERSAL`
+    fn exchange(&mut self, a: usize, b: usize) -> bool {
+        let reach = |from: usize, to: usize| {
+            let mut city = from;
+            for _ in 0..MAX_REVERSAL {
+                if city == to {
+                    return true;
+                }
+                city = self.next[city];
+            }
+            false
+        };
+
+        if reach(self.next[a], b) {
+            self.reverse(a, b);
+        } else if reach(self.next[b], a) {
+            self.reverse(b, a);
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    // reverse the stretch from the successor of `a` up to `b`
+    fn reverse(&mut self, a: usize, b: usize) {
+        let (first, after) = (self.next[a], self.next[b]);
+        self.flip(first, b);
+
+        self.next[a] = b;
+        self.prev[b] = a;
+        self.next[first] = after;
+        self.prev[after] = first;
+    }
+
+    // turn the links of the stretch from `first` to `last` around, its ends are left to the caller
+    fn flip(&mut self, first: usize, last: usize) {
+        let mut city = first;
+        loop {
+            let following = self.next[city];
+            std::mem::swap(&mut self.next[city], &mut self.prev[city]);
+            if city == last {
+                break;
+            }
+            city = following;
+        }
+    }
+
+    // move up to `SEGMENT` stops starting at `city` between one of its neighbours
+    // and the stop next to it, either way round
+    fn or_opt(&mut self, city: usize, neighbours: &[usize]) -> Option<Vec<usize>> {
+        let mut last = city;
+        for _ in 0..SEGMENT {
+            let (before, after) = (self.prev[city], self.next[last]);
+            if after == before || after == city {
+                break;
+            }
+            let removed = self.distance(before, city) + self.distance(last, after) - self.distance(before, after);
+
+            for &other in neighbours {
+                if self.in_segment(city, last, other) {
+                    continue;
+                }
+                for (c, d) in [(other, self.next[other]), (self.prev[other], other)] {
+                    if self.in_segment(city, last, c) || self.in_segment(city, last, d) {
+                        continue;
+                    }
+                    let forward = self.distance(c, city) + self.distance(last, d);
+                    let backward = self.distance(c, last) + self.distance(city, d);
+                    let added = forward.min(backward) - self.distance(c, d);
+                    if removed - added > EPSILON {
+                        self.move_segment(city, last, c, d, backward < forward);
+                        return Some(vec![before, after, c, d, last]);
+                    }
+                }
+            }
+
+            last = after;
+        }
+
+        None
+    }
+
+    fn in_segment(&self, first: usize, last: usize, city: usize) -> bool {
+        let mut current = first;
+        loop {
+            if current == city {
+                return true;
+            }
+            if current == last {
+                return false;
+            }
+            current = self.next[current];
+        }
+    }
+
+    // take the stops from `first` to `last` out and put them between `c` and its successor `d`
+    fn move_segment(&mut self, first: usize, last: usize, c: usize, d: usize, reversed: bool) {
+        let (before, after) = (self.prev[first], self.next[last]);
+        self.next[before] = after;
+        self.prev[after] = before;
+
+        let (head, tail) = match reversed {
+            true => {
+                self.flip(first, last);
+                (last, first)
+            },
+            false => (first, last),
+        };
+
+        self.next[c] = head;
+        self.prev[head] = c;
+        self.next[tail] = d;
+        self.prev[d] = tail;
+    }
+}
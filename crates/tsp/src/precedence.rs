@@ -0,0 +1,259 @@
+use std::error::Error;
+use std::fmt;
+use std::slice;
+
+use rand::RngCore;
+use rand::seq::SliceRandom;
+
+use crate::route::Route;
+use crate::observer::{ SolverEvent, SolverObserver };
+use crate::utils::{ euclidean_distance, removal_saving };
+
+// tours are read starting from the depot, the first city
+const DEPOT: usize = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrecedenceError {
+    // `after` is visited at stop `after_stop` but `before` only at stop `before_stop`
+    Violated { before: usize, after: usize, before_stop: usize, after_stop: usize },
+    // a constraint refers to a city the tour does not visit
+    Missing { city: usize },
+    // nothing can come before the depot, the tour starts there
+    BeforeDepot { city: usize },
+    // the constraints ask for `city` to come before itself
+    Cycle { city: usize }
+}
+
+impl fmt::Display for PrecedenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrecedenceError::Violated { before, after, before_stop, after_stop } => write!(
+                f,
+                "city {} is visited at stop {} but it has to come after city {}, which is only visited at stop {}",
+                after, after_stop, before, before_stop
+            ),
+            PrecedenceError::Missing { city } => write!(f, "city {} has a precedence constraint but is not on the tour", city),
+            PrecedenceError::BeforeDepot { city } => write!(f, "city {} can not come before the depot where the tour starts", city),
+            PrecedenceError::Cycle { city } => write!(f, "the precedence constraints require city {} to come before itself", city),
+        }
+    }
+}
+
+impl Error for PrecedenceError {}
+
+// pickup and delivery style constraints, the first city of every pair
+// has to be visited before the second one, counting from the depot
+pub struct Precedence {
+    pub pairs: Vec<(usize, usize)>
+}
+
+impl Precedence {
+    pub fn new(pairs: Vec<(usize, usize)>) -> Precedence {
+        Precedence { pairs }
+    }
+
+    pub fn validate(&self, route: &Route) -> Result<(), PrecedenceError> {
+        let stops = stops(&route.indices);
+
+        for &(before, after) in &self.pairs {
+            if after == DEPOT {
+                return Err(PrecedenceError::BeforeDepot { city: before });
+            }
+            let stop = |city: usize| stops.get(city).cloned().flatten().ok_or(PrecedenceError::Missing { city });
+            let before_stop = stop(before)?;
+            let after_stop = stop(after)?;
+            if before_stop >= after_stop {
+                return Err(PrecedenceError::Violated { before, after, before_stop, after_stop });
+            }
+        }
+
+        Ok(())
+    }
+
+    // cheapest insertion of the cities in an order that respects every constraint,
+    // a city is only inserted after all of the cities it has to follow
+    pub fn feasible_route(&self, cities: &[(i32, i32)]) -> Result<Route, PrecedenceError> {
+        for &(before, after) in &self.pairs {
+            if after == DEPOT {
+                return Err(PrecedenceError::BeforeDepot { city: before });
+            }
+            if before >= cities.len() || after >= cities.len() {
+                return Err(PrecedenceError::Missing { city: before.max(after) });
+            }
+        }
+
+        let mut tour = vec![DEPOT];
+        let mut inserted = vec![false; cities.len()];
+        inserted[DEPOT] = true;
+
+        while tour.len() < cities.len() {
+            let ready: Vec<usize> = (0..cities.len())
+                .filter(|&city| !inserted[city])
+                .filter(|&city| self.pairs.iter().all(|&(before, after)| after != city || inserted[before]))
+                .collect();
+            if ready.is_empty() {
+                let city = (0..cities.len()).find(|&city| !inserted[city]).unwrap();
+                return Err(PrecedenceError::Cycle { city });
+            }
+
+            for city in ready {
+                // right after the last city it has to follow, or anywhere after the depot
+                let earliest = self.pairs.iter()
+                    .filter(|&&(_, after)| after == city)
+                    .map(|&(before, _)| tour.iter().position(|&stop| stop == before).unwrap() + 1)
+                    .max()
+                    .unwrap_or(1);
+                let position = (earliest..=tour.len())
+                    .min_by(|&a, &b| {
+                        insertion(cities, &tour, a, city).partial_cmp(&insertion(cities, &tour, b, city)).unwrap()
+                    })
+                    .unwrap();
+                tour.insert(position, city);
+                inserted[city] = true;
+            }
+        }
+
+        let routes = tour.iter().map(|&city| cities[city]).collect();
+        Ok(Route::with_indices(routes, tour))
+    }
+
+    // would the route still be feasible after `route.swap_edges(index_a, index_b)`
+    pub fn allows_edges_swap(&self, route: &Route, index_a: usize, index_b: usize) -> bool {
+        let lower = index_a.min(index_b);
+        let higher = index_a.max(index_b);
+        let length = route.indices.len();
+        let swapped = |position: usize| match position {
+            p if p >= lower && p < higher => lower + higher - 1 - p,
+            p => p,
+        };
+
+        let positions = positions(&route.indices);
+        let position = |city: usize| positions.get(city).cloned().flatten();
+        let depot = swapped(position(DEPOT).unwrap_or(0));
+        let stop = |city: usize| position(city).map(|position| (swapped(position) + length - depot) % length);
+
+        self.pairs.iter().all(|&(before, after)| match (stop(before), stop(after)) {
+            (Some(before), Some(after)) => before < after,
+            _ => false,
+        })
+    }
+
+    // would the route still be feasible after `route.move_stop(from, to)`
+    pub fn allows_move_stop(&self, route: &Route, from: usize, to: usize) -> bool {
+        let mut indices = route.indices.clone();
+        let city = indices.remove(from);
+        indices.insert(to, city);
+
+        let stops = stops(&indices);
+        let stop = |city: usize| stops.get(city).cloned().flatten();
+        self.pairs.iter().all(|&(before, after)| match (stop(before), stop(after)) {
+            (Some(before), Some(after)) => before < after,
+            _ => false,
+        })
+    }
+}
+
+// 2-opt and single stop moves that never break the precedence constraints,
+// `route` has to be feasible to begin with
+pub fn precedence_edges_swap(
+    route: &mut Route,
+    precedence: &Precedence,
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> Result<(), PrecedenceError> {
+    precedence.validate(route)?;
+    let mut iteration = 0;
+
+    loop {
+        iteration += 1;
+        observer.notify(&SolverEvent::Iteration { iteration, distance: route.total_distance() });
+
+        if !feasible_edges_swap(route, precedence, rng) && !feasible_move_stop(route, precedence) {
+            break;
+        }
+
+        observer.notify(&SolverEvent::Improvement {
+            tours: slice::from_ref(route),
+            distance: route.total_distance()
+        });
+    }
+
+    observer.notify(&SolverEvent::Finished {
+        tours: slice::from_ref(route),
+        distance: route.total_distance()
+    });
+    Ok(())
+}
+
+// random pickup and delivery pairs, every city belongs to at most one pair
+pub fn generate_pairs(n_cities: usize, amount: usize, rng: &mut dyn RngCore) -> Vec<(usize, usize)> {
+    let mut cities: Vec<usize> = (0..n_cities).filter(|&city| city != DEPOT).collect();
+    cities.shuffle(rng);
+
+    cities.chunks_exact(2).take(amount).map(|pair| (pair[0], pair[1])).collect()
+}
+
+fn feasible_edges_swap(route: &mut Route, precedence: &Precedence, rng: &mut dyn RngCore) -> bool {
+    for (a, b) in route.possible_pairings(rng) {
+        let improves = route.edges_swap_gain(a, b).is_some_and(|gain| gain > 1e-3);
+        if improves && precedence.allows_edges_swap(route, a, b) {
+            route.swap_edges(a, b);
+            return true;
+        }
+    }
+
+    false
+}
+
+// take a single stop out and put it back where the route gets shorter
+fn feasible_move_stop(route: &mut Route, precedence: &Precedence) -> bool {
+    let length = route.routes.len();
+
+    for from in 0..length {
+        let saving = removal_saving(&route.routes, from);
+        let mut rest = route.routes.clone();
+        let city = rest.remove(from);
+
+        for to in 0..rest.len() {
+            let prev = &rest[(to + rest.len() - 1) % rest.len()];
+            let next = &rest[to];
+            let cost = euclidean_distance(prev, &city) + euclidean_distance(&city, next)
+                - euclidean_distance(prev, next);
+
+            if saving - cost > 1e-3 && precedence.allows_move_stop(route, from, to) {
+                route.move_stop(from, to);
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+// position of every city in `indices`
+fn positions(indices: &[usize]) -> Vec<Option<usize>> {
+    let mut positions = vec![None; indices.iter().max().map_or(0, |max| max + 1)];
+    for (position, &city) in indices.iter().enumerate() {
+        positions[city] = Some(position);
+    }
+
+    positions
+}
+
+// stop number of every city counting from the depot, or from the first stop without one
+fn stops(indices: &[usize]) -> Vec<Option<usize>> {
+    let positions = positions(indices);
+    let depot = positions.get(DEPOT).cloned().flatten().unwrap_or(0);
+
+    positions.iter()
+        .map(|position| position.map(|position| (position + indices.len() - depot) % indices.len()))
+        .collect()
+}
+
+// extra length when inserting `city` right before `tour[position]`, the end closes back to the depot
+fn insertion(cities: &[(i32, i32)], tour: &[usize], position: usize, city: usize) -> f32 {
+    let prev = &cities[tour[position - 1]];
+    let next = &cities[tour[position % tour.len()]];
+
+    euclidean_distance(prev, &cities[city]) + euclidean_distance(&cities[city], next) - euclidean_distance(prev, next)
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+use std::slice;
+
+use rand::RngCore;
+
+use crate::algorithms::edges_swap;
+use crate::route::Route;
+use crate::observer::{ SolverEvent, SolverObserver, Silent };
+use crate::utils::{ insertion_cost, removal_saving };
+
+// the first city is the depot, every tour starts from it and it is never dropped
+const DEPOT: usize = 0;
+
+pub struct PrizeReport {
+    pub route: Route,
+    pub visited: Vec<usize>,
+    pub skipped: Vec<usize>,
+    pub prize: i32,
+    pub penalty: i32,
+    pub distance: f32
+}
+
+impl PrizeReport {
+    fn new(route: Route, prizes: &[i32]) -> PrizeReport {
+        let mut visited = route.indices.clone();
+        visited.sort_unstable();
+        let skipped: Vec<usize> = (0..prizes.len())
+            .filter(|city| visited.binary_search(city).is_err())
+            .collect();
+
+        PrizeReport {
+            prize: visited.iter().map(|&city| prizes[city]).sum(),
+            penalty: skipped.iter().map(|&city| prizes[city]).sum(),
+            distance: route.total_distance(),
+            visited,
+            skipped,
+            route
+        }
+    }
+}
+
+impl fmt::Display for PrizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Visited Cities ({}) : {:?}", self.visited.len(), self.route.indices)?;
+        writeln!(f, "Skipped Cities ({}) : {:?}", self.skipped.len(), self.skipped)?;
+        writeln!(f, "Collected Prize : {}", self.prize)?;
+        writeln!(f, "Skipped Penalty : {}", self.penalty)?;
+        write!(f, "Distance : {}", self.distance)
+    }
+}
+
+// Orienteering Problem, collect as much prize as possible
+// while keeping the tour length within `budget`
+pub fn orienteering(
+    cities: &[(i32, i32)],
+    prizes: &[i32],
+    budget: f32,
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> PrizeReport {
+    let mut route = depot_route(cities);
+    let mut iteration = 0;
+    let mut restart = 0;
+
+    loop {
+        iteration += 1;
+        observer.notify(&SolverEvent::Iteration { iteration, distance: route.total_distance() });
+
+        if add_within_budget(&mut route, cities, prizes, budget, None, rng) {
+            notify_improvement(observer, &route);
+            continue;
+        }
+
+        // drop move: give up a visited city when the freed length
+        // lets the tour collect more prize (or the same prize shorter)
+        let current = (collected(&route, prizes), route.total_distance());
+        let mut improved = false;
+
+        for position in drop_candidates(&route, prizes) {
+            let dropped = route.indices[position];
+            let mut candidate = route.clone();
+            candidate.remove_at(position);
+            optimise(&mut candidate, rng);
+            while add_within_budget(&mut candidate, cities, prizes, budget, Some(dropped), rng) {}
+
+            let result = (collected(&candidate, prizes), candidate.total_distance());
+            if result.0 > current.0 || (result.0 == current.0 && result.1 < current.1 - 1e-3) {
+                route = candidate;
+                improved = true;
+                restart += 1;
+                observer.notify(&SolverEvent::Restart { restart, distance: route.total_distance() });
+                notify_improvement(observer, &route);
+                break;
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    observer.notify(&SolverEvent::Finished {
+        tours: slice::from_ref(&route),
+        distance: route.total_distance()
+    });
+    PrizeReport::new(route, prizes)
+}
+
+// Prize-Collecting TSP, every skipped city costs its penalty,
+// minimise the tour length plus the penalties of the skipped cities
+pub fn prize_collecting(
+    cities: &[(i32, i32)],
+    penalties: &[i32],
+    rng: &mut dyn RngCore,
+    observer: &mut dyn SolverObserver
+) -> PrizeReport {
+    // start by visiting everything, then trade cities for their penalties
+    let mut route = Route::new(cities.to_vec());
+    let mut visited = vec![true; cities.len()];
+    let mut iteration = 0;
+    optimise(&mut route, rng);
+
+    loop {
+        iteration += 1;
+        observer.notify(&SolverEvent::Iteration { iteration, distance: route.total_distance() });
+
+        // add move: visiting a city is worth it when it costs less than its penalty
+        let mut best_add: Option<(usize, usize, f32)> = None;
+        for city in (0..cities.len()).filter(|&city| !visited[city]) {
+            let (position, cost) = insertion_cost(&route.routes, &cities[city]);
+            let gain = penalties[city] as f32 - cost;
+            if gain > 1e-3 && best_add.is_none_or(|best| gain > best.2) {
+                best_add = Some((city, position, gain));
+            }
+        }
+
+        // drop move: skipping a city is worth it when it saves more than its penalty
+        let mut best_drop: Option<(usize, f32)> = None;
+        for position in 0..route.routes.len() {
+            if route.indices[position] == DEPOT {
+                continue;
+            }
+            let gain = removal_saving(&route.routes, position) - penalties[route.indices[position]] as f32;
+            if gain > 1e-3 && best_drop.is_none_or(|best| gain > best.1) {
+                best_drop = Some((position, gain));
+            }
+        }
+
+        match (best_add, best_drop) {
+            (Some((city, position, add_gain)), drop) if drop.is_none_or(|drop| add_gain >= drop.1) => {
+                route.insert_at(position, cities[city], city);
+                visited[city] = true;
+            },
+            (_, Some((position, _))) => {
+                visited[route.indices[position]] = false;
+                route.remove_at(position);
+            },
+            _ => break,
+        }
+
+        optimise(&mut route, rng);
+        notify_improvement(observer, &route);
+    }
+
+    observer.notify(&SolverEvent::Finished {
+        tours: slice::from_ref(&route),
+        distance: route.total_distance()
+    });
+    PrizeReport::new(route, penalties)
+}
+
+fn notify_improvement(observer: &mut dyn SolverObserver, route: &Route) {
+    observer.notify(&SolverEvent::Improvement {
+        tours: slice::from_ref(route),
+        distance: route.total_distance()
+    });
+}
+
+fn depot_route(cities: &[(i32, i32)]) -> Route {
+    Route::with_indices(vec![cities[DEPOT]], vec![DEPOT])
+}
+
+fn optimise(route: &mut Route, rng: &mut dyn RngCore) {
+    edges_swap(route, rng, &mut Silent);
+}
+
+fn collected(route: &Route, prizes: &[i32]) -> i32 {
+    route.indices.iter().map(|&city| prizes[city]).sum()
+}
+
+// add the unvisited city with the best prize per added length that still fits the budget
+fn add_within_budget(
+    route: &mut Route,
+    cities: &[(i32, i32)],
+    prizes: &[i32],
+    budget: f32,
+    excluded: Option<usize>,
+    rng: &mut dyn RngCore
+) -> bool {
+    let distance = route.total_distance();
+    let mut best: Option<(usize, usize, f32)> = None;
+
+    for city in 0..cities.len() {
+        if Some(city) == excluded || route.indices.contains(&city) {
+            continue;
+        }
+        let (position, cost) = insertion_cost(&route.routes, &cities[city]);
+        if distance + cost > budget {
+            continue;
+        }
+        let ratio = prizes[city] as f32 / cost.max(1e-3);
+        if best.is_none_or(|best| ratio > best.2) {
+            best = Some((city, position, ratio));
+        }
+    }
+
+    match best {
+        Some((city, position, _)) => {
+            route.insert_at(position, cities[city], city);
+            optimise(route, rng);
+            true
+        },
+        None => false,
+    }
+}
+
+// visited cities ordered from the cheapest to give up
+fn drop_candidates(route: &Route, prizes: &[i32]) -> Vec<usize> {
+    let mut positions: Vec<usize> = (0..route.routes.len())
+        .filter(|&position| route.indices[position] != DEPOT)
+        .collect();
+    positions.sort_by(|&a, &b| {
+        let ratio_a = prizes[route.indices[a]] as f32 / removal_saving(&route.routes, a).max(1e-3);
+        let ratio_b = prizes[route.indices[b]] as f32 / removal_saving(&route.routes, b).max(1e-3);
+        ratio_a.partial_cmp(&ratio_b).unwrap()
+    });
+
+    positions
+}
@@ -0,0 +1,320 @@
+use std::iter;
+use std::sync::Arc;
+
+use numpy::ndarray::Array2;
+use numpy::{ PyArray1, PyArray2, PyReadonlyArray1, PyReadonlyArray2 };
+use pyo3::exceptions::{ PyIOError, PyValueError };
+use pyo3::prelude::*;
+use rand::{ Rng, SeedableRng };
+use rand_pcg::Pcg64;
+
+use crate::algorithms::edges_swap;
+use crate::graph::Graph;
+use crate::mtsp;
+use crate::observer::Silent;
+use crate::precedence::{ Precedence, precedence_edges_swap };
+use crate::prize;
+use crate::result;
+use crate::route::Route;
+use crate::utils::{ Config, Objective, generate_cities };
+
+// names match the `algorithm` of a saved solve result
+const SOLVERS: [&str; 5] = ["2-opt", "precedence 2-opt", "multiple salesmen", "orienteering", "prize collecting"];
+
+// `Route` seen from python, coordinates and indices come out as numpy arrays
+#[pyclass(name = "Route", module = "tsp")]
+#[derive(Clone)]
+pub struct PyRoute {
+    route: Route
+}
+
+#[pymethods]
+impl PyRoute {
+    // `coordinates` is an (n, 2) array, `indices` default to 0..n
+    #[new]
+    #[pyo3(signature = (coordinates, indices = None))]
+    fn new(coordinates: PyReadonlyArray2<'_, i32>, indices: Option<PyReadonlyArray1<'_, usize>>) -> PyResult<PyRoute> {
+        let cities = read_cities(&coordinates)?;
+        let indices = match indices {
+            Some(indices) => indices.as_array().to_vec(),
+            None => (0..cities.len()).collect(),
+        };
+        if indices.len() != cities.len() {
+            return Err(PyValueError::new_err("coordinates and indices must have the same length"));
+        }
+
+        Ok(PyRoute { route: Route::with_indices(cities, indices) })
+    }
+
+    #[getter]
+    fn coordinates<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<i32>> {
+        cities_array(py, &self.route.routes)
+    }
+
+    #[getter]
+    fn indices<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<usize>> {
+        PyArray1::from_vec(py, self.route.indices.clone())
+    }
+
+    fn total_distance(&self) -> f32 {
+        self.route.total_distance()
+    }
+
+    // json for a `.json` path, bincode otherwise
+    fn save(&self, path: &str) -> PyResult<()> {
+        result::save(&self.route, path).map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<PyRoute> {
+        result::load(path)
+            .map(|route| PyRoute { route })
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    fn __len__(&self) -> usize {
+        self.route.routes.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Route(cities={}, distance={})", self.route.routes.len(), self.route.total_distance())
+    }
+}
+
+#[pyfunction]
+fn solvers() -> Vec<&'static str> {
+    SOLVERS.to_vec()
+}
+
+// the same cities the `tsp` command generates for this seed
+#[pyfunction]
+#[pyo3(signature = (n_city, border, seed = None))]
+fn random_cities(py: Python<'_>, n_city: i32, border: i32, seed: Option<u64>) -> Bound<'_, PyArray2<i32>> {
+    let mut config = Config::new(iter::empty()).unwrap();
+    config.n_city = n_city;
+    config.border = border;
+
+    let cities = generate_cities(&config, &mut seeded(seed));
+    cities_array(py, &cities)
+}
+
+// stops of a road network as a route over their shortest path distances,
+// the indices of the route are positions in `stops`
+#[pyfunction]
+fn load_road_network(path: &str, stops: Vec<usize>) -> PyResult<PyRoute> {
+    let graph = Graph::from_file(path).map_err(|err| PyIOError::new_err(err.to_string()))?;
+    let matrix = graph.metric_closure(&stops).map_err(|err| PyValueError::new_err(err.to_string()))?;
+    let coordinates = stops.iter()
+        .map(|&stop| graph.coordinates.as_ref().map_or((0, 0), |coordinates| coordinates[stop]))
+        .collect();
+
+    Ok(PyRoute { route: Route::with_matrix(coordinates, (0..stops.len()).collect(), Arc::new(matrix)) })
+}
+
+#[pyfunction]
+#[pyo3(signature = (route, seed = None))]
+fn two_opt(py: Python<'_>, route: &PyRoute, seed: Option<u64>) -> PyRoute {
+    let mut route = route.route.clone();
+    py.detach(|| edges_swap(&mut route, &mut seeded(seed), &mut Silent));
+
+    PyRoute { route }
+}
+
+// `pairs` are (pickup, delivery) city indices, the depot is city 0
+#[pyfunction]
+#[pyo3(signature = (coordinates, pairs, seed = None))]
+fn pickup_delivery(
+    py: Python<'_>,
+    coordinates: PyReadonlyArray2<'_, i32>,
+    pairs: Vec<(usize, usize)>,
+    seed: Option<u64>
+) -> PyResult<PyRoute> {
+    let cities = read_cities(&coordinates)?;
+    let precedence = Precedence::new(pairs);
+
+    py.detach(|| {
+        let mut route = precedence.feasible_route(&cities)?;
+        precedence_edges_swap(&mut route, &precedence, &mut seeded(seed), &mut Silent)?;
+        Ok(PyRoute { route })
+    })
+    .map_err(|err: crate::precedence::PrecedenceError| PyValueError::new_err(err.to_string()))
+}
+
+// `objective` is either "sum" or "max"
+#[pyfunction]
+#[pyo3(signature = (coordinates, salesmen, objective = "sum", seed = None))]
+fn multiple_salesmen(
+    py: Python<'_>,
+    coordinates: PyReadonlyArray2<'_, i32>,
+    salesmen: usize,
+    objective: &str,
+    seed: Option<u64>
+) -> PyResult<Vec<PyRoute>> {
+    let cities = read_cities(&coordinates)?;
+    let objective = match objective {
+        "sum" => Objective::MinSum,
+        "max" => Objective::MinMax,
+        _ => return Err(PyValueError::new_err("objective must be either sum or max")),
+    };
+
+    let tours = py.detach(|| mtsp::multiple_salesmen(&cities, salesmen, objective, &mut seeded(seed), &mut Silent))
+        .map_err(PyValueError::new_err)?;
+    Ok(tours.into_iter().map(|route| PyRoute { route }).collect())
+}
+
+// the visited cities and the prize they collect
+#[pyfunction]
+#[pyo3(signature = (coordinates, prizes, budget, seed = None))]
+fn orienteering(
+    py: Python<'_>,
+    coordinates: PyReadonlyArray2<'_, i32>,
+    prizes: PyReadonlyArray1<'_, i32>,
+    budget: f32,
+    seed: Option<u64>
+) -> PyResult<(PyRoute, i32)> {
+    let cities = read_cities(&coordinates)?;
+    let prizes = read_values(&prizes, cities.len(), "prizes")?;
+
+    let report = py.detach(|| prize::orienteering(&cities, &prizes, budget, &mut seeded(seed), &mut Silent));
+    Ok((PyRoute { route: report.route }, report.prize))
+}
+
+// the visited cities and the penalty of the skipped ones
+#[pyfunction]
+#[pyo3(signature = (coordinates, penalties, seed = None))]
+fn prize_collecting(
+    py: Python<'_>,
+    coordinates: PyReadonlyArray2<'_, i32>,
+    penalties: PyReadonlyArray1<'_, i32>,
+    seed: Option<u64>
+) -> PyResult<(PyRoute, i32)> {
+    let cities = read_cities(&coordinates)?;
+    let penalties = read_values(&penalties, cities.len(), "penalties")?;
+
+    let report = py.detach(|| prize::prize_collecting(&cities, &penalties, &mut seeded(seed), &mut Silent));
+    Ok((PyRoute { route: report.route }, report.penalty))
+}
+
+#[pymodule]
+fn tsp(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyRoute>()?;
+    module.add_function(wrap_pyfunction!(solvers, module)?)?;
+    module.add_function(wrap_pyfunction!(random_cities, module)?)?;
+    module.add_function(wrap_pyfunction!(load_road_network, module)?)?;
+    module.add_function(wrap_pyfunction!(two_opt, module)?)?;
+    module.add_function(wrap_pyfunction!(pickup_delivery, module)?)?;
+    module.add_function(wrap_pyfunction!(multiple_salesmen, module)?)?;
+    module.add_function(wrap_pyfunction!(orienteering, module)?)?;
+    module.add_function(wrap_pyfunction!(prize_collecting, module)?)?;
+
+    Ok(())
+}
+
+// a random seed when python does not pick one
+fn seeded(seed: Option<u64>) -> Pcg64 {
+    Pcg64::seed_from_u64(seed.unwrap_or_else(|| rand::thread_rng().gen()))
+}
+
+fn read_cities(coordinates: &PyReadonlyArray2<'_, i32>) -> PyResult<Vec<(i32, i32)>> {
+    let coordinates = coordinates.as_array();
+    if coordinates.ncols() != 2 {
+        return Err(PyValueError::new_err("coordinates must be an (n, 2) array"));
+    }
+    // the first city is the depot of every solver
+    if coordinates.nrows() == 0 {
+        return Err(PyValueError::new_err("coordinates must hold at least one city"));
+    }
+
+    Ok(coordinates.rows().into_iter().map(|row| (row[0], row[1])).collect())
+}
+
+fn read_values(values: &PyReadonlyArray1<'_, i32>, length: usize, name: &str) -> PyResult<Vec<i32>> {
+    let values = values.as_array().to_vec();
+    if values.len() != length {
+        return Err(PyValueError::new_err(format!("{} must have one value per city", name)));
+    }
+
+    Ok(values)
+}
+
+fn cities_array<'py>(py: Python<'py>, cities: &[(i32, i32)]) -> Bound<'py, PyArray2<i32>> {
+    let flat = cities.iter().flat_map(|&(x, y)| vec![x, y]).collect();
+    PyArray2::from_owned_array(py, Array2::from_shape_vec((cities.len(), 2), flat).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use pyo3::prelude::*;
+
+    use super::*;
+
+    // the module as python sees it, the numpy parts only run where numpy is installed
+    const SMOKE_TEST: &str = r#"
+import os
+import tempfile
+
+import tsp
+
+assert "2-opt" in tsp.solvers()
+
+folder = tempfile.mkdtemp()
+path = os.path.join(folder, "square.csv")
+with open(path, "w") as roads:
+    roads.write("from,to,weight\n0,1,3\n1,2,4\n2,3,3\n3,0,4\n0,2,5\n1,3,5\n")
+
+route = tsp.load_road_network(path, [0, 2, 1, 3])
+assert len(route) == 4
+best = tsp.two_opt(route, seed=7)
+assert abs(best.total_distance() - 14) < 1e-3, best
+
+saved = os.path.join(folder, "route.json")
+best.save(saved)
+assert len(tsp.Route.load(saved)) == 4
+
+try:
+    tsp.load_road_network(path, [0, 99])
+    raise AssertionError("a stop outside the graph was accepted")
+except ValueError:
+    pass
+
+try:
+    import numpy as np
+except ImportError:
+    np = None
+
+if np is not None:
+    empty = np.zeros((0, 2), dtype=np.int32)
+    nothing = np.zeros(0, dtype=np.int32)
+    for call in [
+        lambda: tsp.Route(empty),
+        lambda: tsp.multiple_salesmen(empty, 2),
+        lambda: tsp.orienteering(empty, nothing, 10.0),
+        lambda: tsp.prize_collecting(empty, nothing),
+    ]:
+        try:
+            call()
+            raise AssertionError("empty coordinates were accepted")
+        except ValueError:
+            pass
+
+    cities = tsp.random_cities(30, 1000, seed=1)
+    assert len(tsp.multiple_salesmen(cities, 3, seed=1)) == 3
+    assert len(tsp.two_opt(tsp.Route(cities), seed=1)) == len(cities)
+"#;
+
+    #[test]
+    fn bindings_smoke_test() {
+        pyo3::append_to_inittab!(tsp);
+        Python::initialize();
+
+        Python::attach(|py| {
+            let code = CString::new(SMOKE_TEST).unwrap();
+            if let Err(err) = py.run(&code, None, None) {
+                err.print(py);
+                panic!("the python smoke test failed");
+            }
+        });
+    }
+}
@@ -0,0 +1,70 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{ Serialize, Deserialize };
+use serde::de::DeserializeOwned;
+
+type SerdeResult<T> = Result<T, Box<dyn Error>>;
+
+// what a finished run reports to other services,
+// several tours are joined into one, each of them starting at the depot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolveResult {
+    pub tour: Vec<usize>,
+    pub distance: f32,
+    // seconds spent solving
+    pub runtime: f64,
+    pub algorithm: String,
+    pub seed: u64
+}
+
+impl SolveResult {
+    pub fn new(tour: Vec<usize>, distance: f32, runtime: Duration, algorithm: &str, seed: u64) -> SolveResult {
+        SolveResult {
+            tour,
+            distance,
+            runtime: runtime.as_secs_f64(),
+            algorithm: algorithm.to_string(),
+            seed
+        }
+    }
+}
+
+pub fn to_json<T: Serialize>(value: &T) -> SerdeResult<String> {
+    Ok(serde_json::to_string_pretty(value)?)
+}
+
+pub fn from_json<T: DeserializeOwned>(text: &str) -> SerdeResult<T> {
+    Ok(serde_json::from_str(text)?)
+}
+
+pub fn to_bytes<T: Serialize>(value: &T) -> SerdeResult<Vec<u8>> {
+    Ok(bincode::serialize(value)?)
+}
+
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> SerdeResult<T> {
+    Ok(bincode::deserialize(bytes)?)
+}
+
+// `.json` files are written as json, anything else in the binary format
+pub fn save<T: Serialize>(value: &T, path: &str) -> SerdeResult<()> {
+    match is_json(path) {
+        true => fs::write(path, to_json(value)?)?,
+        false => fs::write(path, to_bytes(value)?)?,
+    }
+
+    Ok(())
+}
+
+pub fn load<T: DeserializeOwned>(path: &str) -> SerdeResult<T> {
+    match is_json(path) {
+        true => from_json(&fs::read_to_string(path)?),
+        false => from_bytes(&fs::read(path)?),
+    }
+}
+
+fn is_json(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| extension == "json")
+}
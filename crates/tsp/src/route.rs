@@ -2,70 +2,238 @@ use crate::utils::{
     euclidean_distance,
     get_all_possible_pairings
 };
-use rand::{ thread_rng };
+use rand::RngCore;
 use rand::seq::SliceRandom;
+use serde::{ Serialize, Deserialize };
 use std::cmp;
+use std::sync::{ Arc, OnceLock };
 
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "RouteData")]
 pub struct Route {
     pub routes: Vec<(i32, i32)>,
-    pairings: Vec<(usize, usize)>
+    // original city index of every stop, kept in the same order as `routes`
+    pub indices: Vec<usize>,
+    // travel cost between original city indices, when there is none
+    // the euclidean distance between `routes` coordinates is used
+    matrix: Option<Arc<Vec<Vec<f32>>>>,
+    // only depends on the length, built the first time the edge swap asks for them
+    // so routes of millions of cities stay cheap when nothing swaps edges over the whole route
+    #[serde(skip_serializing)]
+    pairings: OnceLock<Vec<(usize, usize)>>
+}
+
+// the serialized fields of a route
+#[derive(Deserialize)]
+struct RouteData {
+    routes: Vec<(i32, i32)>,
+    indices: Vec<usize>,
+    matrix: Option<Arc<Vec<Vec<f32>>>>
+}
+
+impl From<RouteData> for Route {
+    fn from(data: RouteData) -> Route {
+        let mut route = Route::with_indices(data.routes, data.indices);
+        route.matrix = data.matrix;
+        route
+    }
 }
 
 impl Route {
     pub fn new(cities: Vec<(i32, i32)>) -> Route {
-        let pairings = get_all_possible_pairings(&cities);
+        let indices = (0..cities.len()).collect();
+        Route::with_indices(cities, indices)
+    }
+
+    pub fn with_indices(cities: Vec<(i32, i32)>, indices: Vec<usize>) -> Route {
         Route {
             routes: cities,
-            pairings: pairings
+            indices,
+            matrix: None,
+            pairings: OnceLock::new()
+        }
+    }
+
+    pub fn with_matrix(
+        cities: Vec<(i32, i32)>,
+        indices: Vec<usize>,
+        matrix: Arc<Vec<Vec<f32>>>
+    ) -> Route {
+        let mut route = Route::with_indices(cities, indices);
+        route.matrix = Some(matrix);
+        route
+    }
+
+    // travel cost between the stops at positions `a` and `b`
+    pub fn distance(&self, a: usize, b: usize) -> f32 {
+        match &self.matrix {
+            Some(matrix) => matrix[self.indices[a]][self.indices[b]],
+            None => euclidean_distance(&self.routes[a], &self.routes[b]),
         }
     }
 
     pub fn total_distance(&self) -> f32 {
         let mut distance = 0.0;
-        for (i, route) in self.routes.iter().enumerate() {
+        for i in 0..self.routes.len() {
             let prev_i = ((i + self.routes.len()) - 1) % self.routes.len();
-            distance += euclidean_distance(route, &self.routes[prev_i]);
+            distance += self.distance(prev_i, i);
         }
 
         distance
     }
 
-    pub fn possible_pairings(&self) -> Vec<(usize, usize)> {
-        let mut results = self.pairings.clone();
-        results.shuffle(&mut thread_rng());
+    // travel cost between a city that is not on the route yet and the stop at `position`
+    fn distance_to(&self, city: &(i32, i32), index: usize, position: usize) -> f32 {
+        match &self.matrix {
+            Some(matrix) => matrix[index][self.indices[position]],
+            None => euclidean_distance(city, &self.routes[position]),
+        }
+    }
+
+    // put `city` right before the stop at `position`
+    pub fn insert_at(&mut self, position: usize, city: (i32, i32), index: usize) {
+        let length = self.routes.len();
+        self.routes.insert(position, city);
+        self.indices.insert(position, index);
+
+        // only the pairs of the new last position are missing
+        if let Some(pairings) = self.pairings.get_mut() {
+            pairings.extend((0..length).map(|i| (i, length)));
+        }
+    }
+
+    // put `city` where it makes the route the least longer, returns its position
+    pub fn insert_cheapest(&mut self, city: (i32, i32), index: usize) -> usize {
+        let length = self.routes.len();
+        let mut best = (0, f32::MAX);
+
+        for position in 0..length {
+            let prev = (position + length - 1) % length;
+            let cost = self.distance_to(&city, index, prev) + self.distance_to(&city, index, position)
+                - self.distance(prev, position);
+            if cost < best.1 {
+                best = (position, cost);
+            }
+        }
+
+        self.insert_at(best.0, city, index);
+        best.0
+    }
+
+    // take the stop at `position` out, its neighbours get connected to each other
+    pub fn remove_at(&mut self, position: usize) -> ((i32, i32), usize) {
+        let city = self.routes.remove(position);
+        let index = self.indices.remove(position);
+
+        // the pairs of the old last position are the ones at the end
+        let length = self.routes.len();
+        if let Some(pairings) = self.pairings.get_mut() {
+            pairings.truncate(pairings.len() - length);
+        }
+
+        (city, index)
+    }
+
+    // take the city with the original `index` out, returns where it was
+    pub fn remove_city(&mut self, index: usize) -> Option<usize> {
+        let position = self.indices.iter().position(|&stop| stop == index)?;
+        self.remove_at(position);
+
+        Some(position)
+    }
+
+    // the same stops visited in `order`, given as current positions
+    pub fn reordered(&self, order: &[usize]) -> Route {
+        let mut route = self.clone();
+        route.routes = order.iter().map(|&position| self.routes[position]).collect();
+        route.indices = order.iter().map(|&position| self.indices[position]).collect();
+        route
+    }
+
+    // swap edges touching the stops within `radius` of `position` until none shortens the route,
+    // cheap enough to run after every insert or removal
+    pub fn optimise_around(&mut self, position: usize, radius: usize) -> bool {
+        let length = self.routes.len();
+        let mut improved = false;
+        if length < 4 {
+            return false;
+        }
+
+        loop {
+            let mut swapped = false;
+
+            let start = (position + length - radius % length) % length;
+            'search: for offset in 0..(2 * radius + 1).min(length) {
+                let index_a = (start + offset) % length;
+                for index_b in 0..length {
+                    if self.should_edges_swap(index_a, index_b) {
+                        swapped = true;
+                        break 'search;
+                    }
+                }
+            }
+
+            if !swapped {
+                return improved;
+            }
+            improved = true;
+        }
+    }
+
+    pub fn possible_pairings(&self, rng: &mut dyn RngCore) -> Vec<(usize, usize)> {
+        let mut results = self.pairings.get_or_init(|| get_all_possible_pairings(&self.routes)).clone();
+        results.shuffle(rng);
 
         results
     }
 
     pub fn should_edges_swap(&mut self, index_a: usize, index_b: usize) -> bool {
-        let viable = self.check_swap_viability(index_a, index_b);
-
-        if !viable {
-            false
-        } else {
-            let prev_a = (((index_a as i32 + self.routes.len() as i32) - 1) % self.routes.len() as i32) as usize;
-            let prev_b = (((index_b as i32 + self.routes.len() as i32) - 1) % self.routes.len() as i32) as usize;
-            let current = (
-                euclidean_distance(&self.routes[index_a], &self.routes[prev_a]),
-                euclidean_distance(&self.routes[index_b], &self.routes[prev_b])
-            );
-            let swapped = (
-                euclidean_distance(&self.routes[index_a], &self.routes[index_b]),
-                euclidean_distance(&self.routes[prev_a], &self.routes[prev_b])
-            );
-
-            match (swapped.0 + swapped.1) < (current.0 + current.1) {
-                true => {
-                    let lower = cmp::min(index_a, index_b);
-                    let higher = cmp::max(index_a, index_b);
-
-                    self.routes[lower..higher].reverse();
+        match self.edges_swap_gain(index_a, index_b) {
+            Some(gain) if gain > 0.0 => {
+                self.swap_edges(index_a, index_b);
+                true
+            },
+            _ => false
+        }
+    }
 
-                    true
-                },
-                false => false
-            }
+    // how much shorter the route gets by reconnecting the edges ending at `index_a` and `index_b`,
+    // none when the edges are the same or next to each other
+    pub fn edges_swap_gain(&self, index_a: usize, index_b: usize) -> Option<f32> {
+        if index_a == index_b || !self.check_swap_viability(index_a, index_b) {
+            return None;
         }
+
+        let prev_a = (((index_a as i32 + self.routes.len() as i32) - 1) % self.routes.len() as i32) as usize;
+        let prev_b = (((index_b as i32 + self.routes.len() as i32) - 1) % self.routes.len() as i32) as usize;
+        let current = (
+            self.distance(index_a, prev_a),
+            self.distance(index_b, prev_b)
+        );
+        let swapped = (
+            self.distance(index_a, index_b),
+            self.distance(prev_a, prev_b)
+        );
+
+        Some((current.0 + current.1) - (swapped.0 + swapped.1))
+    }
+
+    // reverse the stops between `index_a` and `index_b`, the higher one excluded
+    pub fn swap_edges(&mut self, index_a: usize, index_b: usize) {
+        let lower = cmp::min(index_a, index_b);
+        let higher = cmp::max(index_a, index_b);
+
+        self.routes[lower..higher].reverse();
+        self.indices[lower..higher].reverse();
+    }
+
+    // take the stop at `from` out of the route and put it back at `to`,
+    // the length stays the same so the pairings are still valid
+    pub fn move_stop(&mut self, from: usize, to: usize) {
+        let city = self.routes.remove(from);
+        let index = self.indices.remove(from);
+        self.routes.insert(to, city);
+        self.indices.insert(to, index);
     }
 
     fn check_swap_viability(&self, a: usize, b: usize) -> bool {
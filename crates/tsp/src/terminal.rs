@@ -0,0 +1,165 @@
+use std::env;
+use std::io::{ self, Write };
+
+use crate::observer::{ SolverEvent, SolverObserver };
+use crate::utils::create_plot;
+
+// every braille character is a 2 x 4 grid of dots
+const DOTS_WIDTH: usize = 2;
+const DOTS_HEIGHT: usize = 4;
+const BRAILLE_BLANK: u32 = 0x2800;
+
+pub struct BrailleCanvas {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>
+}
+
+impl BrailleCanvas {
+    // `width` and `height` are counted in characters
+    pub fn new(width: usize, height: usize) -> BrailleCanvas {
+        BrailleCanvas {
+            width,
+            height,
+            cells: vec![0; width * height]
+        }
+    }
+
+    pub fn dots_width(&self) -> usize {
+        self.width * DOTS_WIDTH
+    }
+
+    pub fn dots_height(&self) -> usize {
+        self.height * DOTS_HEIGHT
+    }
+
+    pub fn set(&mut self, x: usize, y: usize) {
+        if x >= self.dots_width() || y >= self.dots_height() {
+            return;
+        }
+        let bit = match (x % DOTS_WIDTH, y % DOTS_HEIGHT) {
+            (0, 3) => 0x40,
+            (1, 3) => 0x80,
+            (0, row) => 0x01 << row,
+            (_, row) => 0x08 << row,
+        };
+        self.cells[(y / DOTS_HEIGHT) * self.width + x / DOTS_WIDTH] |= bit;
+    }
+
+    // bresenham line between two dots
+    pub fn line(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let (mut x, mut y) = (from.0 as i64, from.1 as i64);
+        let (x1, y1) = (to.0 as i64, to.1 as i64);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let step_x = if x < x1 { 1 } else { -1 };
+        let step_y = if y < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            self.set(x as usize, y as usize);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut frame = String::with_capacity(self.cells.len() * 3 + self.height);
+        for row in self.cells.chunks(self.width) {
+            for cell in row {
+                frame.push(std::char::from_u32(BRAILLE_BLANK + *cell as u32).unwrap_or(' '));
+            }
+            frame.push('\n');
+        }
+
+        frame
+    }
+}
+
+// draws the tours on the terminal every time the solver improves them
+pub struct TerminalRenderer {
+    width: usize,
+    height: usize
+}
+
+impl TerminalRenderer {
+    pub fn new(width: usize, height: usize) -> TerminalRenderer {
+        TerminalRenderer { width, height }
+    }
+
+    // fill the terminal, leaving a line for the caption
+    pub fn fit_terminal() -> TerminalRenderer {
+        let size = |name: &str, default: usize| env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default);
+
+        TerminalRenderer::new(size("COLUMNS", 80), size("LINES", 24).saturating_sub(2).max(1))
+    }
+
+    // `plots` are closed tours as built by `create_plot`,
+    // scaled together to fit the canvas
+    pub fn draw(&self, plots: &[Vec<(f64, f64)>]) -> String {
+        let mut canvas = BrailleCanvas::new(self.width, self.height);
+        let points = plots.iter().flatten();
+        let (min_x, max_x) = points.clone().fold((f64::MAX, f64::MIN), |(min, max), p| (min.min(p.0), max.max(p.0)));
+        let (min_y, max_y) = points.fold((f64::MAX, f64::MIN), |(min, max), p| (min.min(p.1), max.max(p.1)));
+        let scale = |value: f64, min: f64, max: f64, dots: usize| {
+            if max > min {
+                ((value - min) / (max - min) * (dots - 1) as f64).round() as usize
+            } else {
+                dots / 2
+            }
+        };
+        let (dots_width, dots_height) = (canvas.dots_width(), canvas.dots_height());
+        // canvas rows grow downwards, the plot's y axis grows upwards
+        let to_dot = |point: &(f64, f64)| (
+            scale(point.0, min_x, max_x, dots_width),
+            dots_height - 1 - scale(point.1, min_y, max_y, dots_height)
+        );
+
+        for plot in plots {
+            let dots: Vec<(usize, usize)> = plot.iter().map(to_dot).collect();
+            for leg in dots.windows(2) {
+                canvas.line(leg[0], leg[1]);
+            }
+            if let [only] = dots[..] {
+                canvas.set(only.0, only.1);
+            }
+        }
+
+        canvas.render()
+    }
+
+    fn redraw(&self, plots: &[Vec<(f64, f64)>], caption: String) {
+        // move the cursor home and clear the screen before drawing the frame
+        print!("\x1b[H\x1b[2J{}{}", self.draw(plots), caption);
+        io::stdout().flush().unwrap_or(());
+    }
+}
+
+impl SolverObserver for TerminalRenderer {
+    fn notify(&mut self, event: &SolverEvent) {
+        match event {
+            SolverEvent::Improvement { tours, distance } => {
+                let plots: Vec<Vec<(f64, f64)>> = tours.iter().map(|tour| create_plot(&tour.routes)).collect();
+                self.redraw(&plots, format!("Distance : {}", distance));
+            },
+            SolverEvent::Finished { tours, distance } => {
+                let plots: Vec<Vec<(f64, f64)>> = tours.iter().map(|tour| create_plot(&tour.routes)).collect();
+                self.redraw(&plots, format!("Final Distance : {}\n", distance));
+            },
+            _ => {},
+        }
+    }
+}
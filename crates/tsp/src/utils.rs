@@ -1,44 +1,251 @@
-use std::env;
-use rand::{ Rng, thread_rng };
-use rand::seq::SliceRandom;
+use rand::{ Rng, RngCore };
 use plotters::prelude::*;
+use serde::{ Serialize, Deserialize };
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Variant {
+    Tsp,
+    Orienteering,
+    PrizeCollecting
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Objective {
+    MinSum,
+    MinMax
+}
+
+// order the edge swap looks for improving swaps in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Strategy {
+    // the first improving pair of a freshly shuffled pass, then start over
+    Shuffled,
+    First,
+    Best,
+    Neighbour
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub n_city: i32,
     pub border: i32,
-    pub saves: bool
+    pub saves: bool,
+    pub variant: Variant,
+    pub budget: Option<f32>,
+    pub salesmen: usize,
+    pub objective: Objective,
+    pub graph: Option<String>,
+    pub stops: Option<Vec<usize>>,
+    pub terminal: bool,
+    pub pairs: usize,
+    // every random choice of a run comes from this seed
+    pub seed: u64,
+    pub output: Option<String>,
+    pub checkpoint: Option<String>,
+    // passes of the edge swap between two checkpoints
+    pub checkpoint_every: usize,
+    pub resume: Option<String>,
+    pub exact: bool,
+    // 2-opt runs merged by partition crossover, none when 0
+    pub merge: usize,
+    // most cities in a cell of the partitioning solver, not partitioned when 0
+    pub partition: usize,
+    pub strategy: Strategy
 }
 
 impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
-        let n_city: i32 = match args.next() {
-            Some(arg) => arg.parse().unwrap(),
-            None => 30,
-        };
-        let border: i32 = match args.next() {
-            Some(arg) => arg.parse().unwrap(),
-            None => 1000,
-        };
-        let saves: bool = match args.next() {
-            Some(_arg) => true,
-            None => false,
+    pub fn new<I: Iterator<Item = String>>(mut args: I) -> Result<Config, &'static str> {
+        let mut config = Config {
+            n_city: 30,
+            border: 1000,
+            saves: false,
+            variant: Variant::Tsp,
+            budget: None,
+            salesmen: 1,
+            objective: Objective::MinSum,
+            graph: None,
+            stops: None,
+            terminal: false,
+            pairs: 0,
+            seed: rand::thread_rng().gen(),
+            output: None,
+            checkpoint: None,
+            checkpoint_every: 100,
+            resume: None,
+            exact: false,
+            merge: 0,
+            partition: 0,
+            strategy: Strategy::Shuffled
         };
+        let mut position = 0;
 
-        Ok(Config {
-            n_city,
-            border,
-            saves
-        })
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--variant" => {
+                    config.variant = match args.next().as_deref() {
+                        Some("tsp") => Variant::Tsp,
+                        Some("op") => Variant::Orienteering,
+                        Some("pctsp") => Variant::PrizeCollecting,
+                        _ => return Err("variant must be one of tsp, op or pctsp"),
+                    };
+                },
+                "--budget" => {
+                    config.budget = match args.next().map(|arg| arg.parse()) {
+                        Some(Ok(budget)) => Some(budget),
+                        _ => return Err("budget must be a number"),
+                    };
+                },
+                "--salesmen" => {
+                    config.salesmen = match args.next().map(|arg| arg.parse()) {
+                        Some(Ok(salesmen)) if salesmen > 0 => salesmen,
+                        _ => return Err("salesmen must be a positive integer"),
+                    };
+                },
+                "--objective" => {
+                    config.objective = match args.next().as_deref() {
+                        Some("sum") => Objective::MinSum,
+                        Some("max") => Objective::MinMax,
+                        _ => return Err("objective must be either sum or max"),
+                    };
+                },
+                "--strategy" => {
+                    config.strategy = match args.next().as_deref() {
+                        Some("shuffled") => Strategy::Shuffled,
+                        Some("first") => Strategy::First,
+                        Some("best") => Strategy::Best,
+                        Some("neighbour") => Strategy::Neighbour,
+                        _ => return Err("strategy must be either shuffled, first, best or neighbour"),
+                    };
+                },
+                "--graph" => {
+                    config.graph = match args.next() {
+                        Some(path) => Some(path),
+                        None => return Err("graph must be a path to a .gr or edge list file"),
+                    };
+                },
+                "--stops" => {
+                    let stops: Result<Vec<usize>, _> = match args.next() {
+                        Some(stops) => stops.split(',').map(|stop| stop.trim().parse()).collect(),
+                        None => return Err("stops must be a comma separated list of nodes"),
+                    };
+                    config.stops = Some(stops.map_err(|_| "stops must be a comma separated list of nodes")?);
+                },
+                "--terminal" => config.terminal = true,
+                "--precedence" => {
+                    config.pairs = match args.next().map(|arg| arg.parse()) {
+                        Some(Ok(pairs)) => pairs,
+                        _ => return Err("precedence must be the number of pickup and delivery pairs"),
+                    };
+                },
+                "--seed" => {
+                    config.seed = match args.next().map(|arg| arg.parse()) {
+                        Some(Ok(seed)) => seed,
+                        _ => return Err("seed must be an unsigned integer"),
+                    };
+                },
+                "--output" => {
+                    config.output = match args.next() {
+                        Some(path) => Some(path),
+                        None => return Err("output must be a path to write the result to"),
+                    };
+                },
+                "--checkpoint" => {
+                    config.checkpoint = match args.next() {
+                        Some(path) => Some(path),
+                        None => return Err("checkpoint must be a path to save the search to"),
+                    };
+                },
+                "--checkpoint-every" => {
+                    config.checkpoint_every = match args.next().map(|arg| arg.parse()) {
+                        Some(Ok(every)) if every > 0 => every,
+                        _ => return Err("checkpoint-every must be a positive number of passes"),
+                    };
+                },
+                "--resume" => {
+                    config.resume = match args.next() {
+                        Some(path) => Some(path),
+                        None => return Err("resume must be a path to a checkpoint"),
+                    };
+                },
+                "--exact" => config.exact = true,
+                "--merge" => {
+                    config.merge = match args.next().map(|arg| arg.parse()) {
+                        Some(Ok(merge)) if merge > 0 => merge,
+                        _ => return Err("merge must be a positive number of 2-opt runs"),
+                    };
+                },
+                "--partition" => {
+                    config.partition = match args.next().map(|arg| arg.parse()) {
+                        Some(Ok(partition)) if partition > 0 => partition,
+                        _ => return Err("partition must be a positive number of cities per cell"),
+                    };
+                },
+                _ if arg.starts_with("--") => return Err("unknown option, see the README for the options"),
+                _ => {
+                    // positional arguments, any third one turns on saving the gif
+                    match position {
+                        0 => config.n_city = arg.parse().map_err(|_| "number of cities must be an integer")?,
+                        1 => config.border = arg.parse().map_err(|_| "border must be an integer")?,
+                        _ => config.saves = true,
+                    }
+                    position += 1;
+                },
+            }
+        }
+
+        // the other solvers and variants have their own search, they would silently ignore these
+        let other_solver = config.variant != Variant::Tsp
+            || config.graph.is_some()
+            || config.pairs > 0
+            || config.salesmen > 1
+            || config.exact
+            || config.merge > 0
+            || config.partition > 0;
+        let checkpointed = config.checkpoint.is_some() || config.resume.is_some();
+        if checkpointed && other_solver {
+            return Err("checkpoint and resume only apply to the plain 2-opt search, without other solver options");
+        }
+        if config.strategy != Strategy::Shuffled && (other_solver || checkpointed) {
+            return Err("strategy only applies to the plain 2-opt search, without other solver options");
+        }
+
+        Ok(config)
     }
 }
 
 pub fn euclidean_distance(a: &(i32, i32), b: &(i32, i32)) -> f32 {
-    ((i32::pow(a.0 - b.0, 2) + i32::pow(a.1 - b.1, 2)) as f32).sqrt()
+    // squared in i64, borders past 30000 overflow i32
+    ((i64::pow(a.0 as i64 - b.0 as i64, 2) + i64::pow(a.1 as i64 - b.1 as i64, 2)) as f32).sqrt()
 }
 
-pub fn generate_cities(config: &Config) -> Vec<(i32, i32)> {
+// cheapest position to insert `city` into the closed `tour`
+// and how much longer the tour gets
+pub fn insertion_cost(tour: &[(i32, i32)], city: &(i32, i32)) -> (usize, f32) {
+    let mut best = (0, f32::MAX);
+    for position in 0..tour.len() {
+        let prev = &tour[(position + tour.len() - 1) % tour.len()];
+        let next = &tour[position];
+        let cost = euclidean_distance(prev, city) + euclidean_distance(city, next)
+            - euclidean_distance(prev, next);
+        if cost < best.1 {
+            best = (position, cost);
+        }
+    }
+
+    best
+}
+
+// how much shorter the closed `tour` gets when the city at `position` is skipped
+pub fn removal_saving(tour: &[(i32, i32)], position: usize) -> f32 {
+    let prev = &tour[(position + tour.len() - 1) % tour.len()];
+    let next = &tour[(position + 1) % tour.len()];
+    let city = &tour[position];
+
+    euclidean_distance(prev, city) + euclidean_distance(city, next) - euclidean_distance(prev, next)
+}
+
+pub fn generate_cities(config: &Config, rng: &mut dyn RngCore) -> Vec<(i32, i32)> {
     let mut results = vec![];
-    let mut rng = rand::thread_rng();
     for _ in 1..config.n_city {
         results.push(
             (
@@ -51,16 +258,26 @@ pub fn generate_cities(config: &Config) -> Vec<(i32, i32)> {
     results
 }
 
+pub fn generate_prizes(config: &Config, max: i32, rng: &mut dyn RngCore) -> Vec<i32> {
+    let mut results = vec![];
+    for _ in 1..config.n_city {
+        results.push(rng.gen_range(1..=max));
+    }
+
+    results
+}
+
 pub fn get_all_possible_pairings(
     routes: &Vec<(i32, i32)>
 ) -> Vec<(usize, usize)> {
+    // grouped by the higher index so the pairs of the last stop are always at the end,
+    // `Route` relies on it when stops are added or removed
     let mut results = vec![];
-    for i in 0..routes.len() {
-        for j in i+1..routes.len() {
+    for j in 0..routes.len() {
+        for i in 0..j {
             results.push((i, j))
         }
     }
-    results.shuffle(&mut thread_rng());
 
     results
 }
@@ -81,12 +298,13 @@ pub fn create_plot(routes: &Vec<(i32, i32)>) -> Vec<(f64, f64)> {
     plot
 }
 
-pub fn animate_plot(plots: &mut Vec<Vec<(f64, f64)>>, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    let root = BitMapBackend::gif(
-                format!("./crates/tsp/examples/{}_cities.gif", config.n_city),
-                (800, 600),
-                100
-            )?
+// every frame holds one or more closed tours, each drawn in its own colour
+pub fn animate_plot(plots: &mut Vec<Vec<Vec<(f64, f64)>>>, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let path = match config.salesmen {
+        1 => format!("./crates/tsp/examples/{}_cities.gif", config.n_city),
+        salesmen => format!("./crates/tsp/examples/{}_cities_{}_salesmen.gif", config.n_city, salesmen),
+    };
+    let root = BitMapBackend::gif(path, (800, 600), 100)?
         .into_drawing_area();
 
     // last elements is multiplied to last longer on gif
@@ -94,7 +312,7 @@ pub fn animate_plot(plots: &mut Vec<Vec<(f64, f64)>>, config: &Config) -> Result
         plots.push(plots.last().unwrap().to_vec())
     }
 
-    for plot in plots {
+    for tours in plots {
         root.fill(&WHITE)?;
 
         let mut chart = ChartBuilder::on(&root)
@@ -108,15 +326,22 @@ pub fn animate_plot(plots: &mut Vec<Vec<(f64, f64)>>, config: &Config) -> Result
                 -30.0..config.border as f64
             )?;
 
-        chart.draw_series(LineSeries::new(
-            plot.iter().map(|(x, y)| (*x, *y)),
-            &BLUE,
-        ))?;
+        for (i, plot) in tours.iter().enumerate() {
+            let color = match tours.len() {
+                1 => BLUE.to_rgba(),
+                _ => Palette99::pick(i).to_rgba(),
+            };
 
-        chart.draw_series(
-            plot.iter()
-                .map(|(x, y)| Circle::new((*x, *y), 3, BLUE.filled())),
-        )?;
+            chart.draw_series(LineSeries::new(
+                plot.iter().map(|(x, y)| (*x, *y)),
+                &color,
+            ))?;
+
+            chart.draw_series(
+                plot.iter()
+                    .map(|(x, y)| Circle::new((*x, *y), 3, color.filled())),
+            )?;
+        }
 
         root.present()?;
     }
@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::route::Route;
+use crate::utils::euclidean_distance;
+
+// distances are summed in f32, tours of many cities drift a little
+const TOLERANCE: f32 = 1e-3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TourError {
+    // the tour has `found` stops for `expected` cities
+    Length { expected: usize, found: usize },
+    // `city` is visited a second time at stop `position`
+    Duplicate { city: usize, position: usize },
+    // stop `position` refers to a city that does not exist
+    Unknown { city: usize, position: usize },
+    // the coordinates at stop `position` are not those of the city it refers to
+    Coordinates { city: usize, position: usize },
+    // the route reports a different length than the closed tour through its cities
+    Distance { reported: f32, recomputed: f32 }
+}
+
+impl fmt::Display for TourError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TourError::Length { expected, found } => write!(f, "the tour has {} stops for {} cities", found, expected),
+            TourError::Duplicate { city, position } => write!(f, "city {} is visited again at stop {}", city, position),
+            TourError::Unknown { city, position } => write!(f, "stop {} refers to city {} which does not exist", position, city),
+            TourError::Coordinates { city, position } => write!(f, "stop {} is not at the coordinates of city {}", position, city),
+            TourError::Distance { reported, recomputed } => write!(
+                f,
+                "the route reports a length of {} but the closed tour is {} long",
+                reported, recomputed
+            ),
+        }
+    }
+}
+
+impl Error for TourError {}
+
+// `route` visits every one of `cities` exactly once at their own coordinates and
+// its length is the one of the closed tour, back from the last stop to the first;
+// returns that recomputed length, only for routes over euclidean distances
+pub fn validate_tour(route: &Route, cities: &[(i32, i32)]) -> Result<f32, TourError> {
+    if route.indices.len() != cities.len() || route.routes.len() != cities.len() {
+        return Err(TourError::Length { expected: cities.len(), found: route.indices.len().max(route.routes.len()) });
+    }
+
+    validate_stops(route, cities)
+}
+
+// the same checks for tours allowed to skip some of `cities`,
+// like the prize collecting ones or a single salesman's share
+pub fn validate_stops(route: &Route, cities: &[(i32, i32)]) -> Result<f32, TourError> {
+    if route.indices.len() != route.routes.len() {
+        return Err(TourError::Length { expected: route.indices.len(), found: route.routes.len() });
+    }
+
+    let mut visited = vec![false; cities.len()];
+    for (position, (&city, coordinates)) in route.indices.iter().zip(&route.routes).enumerate() {
+        match visited.get_mut(city) {
+            None => return Err(TourError::Unknown { city, position }),
+            Some(true) => return Err(TourError::Duplicate { city, position }),
+            Some(seen) => *seen = true,
+        }
+        if *coordinates != cities[city] {
+            return Err(TourError::Coordinates { city, position });
+        }
+    }
+
+    let recomputed = closed_length(&route.indices, cities);
+    let reported = route.total_distance();
+    if (reported - recomputed).abs() > TOLERANCE * recomputed.max(1.0) {
+        return Err(TourError::Distance { reported, recomputed });
+    }
+
+    Ok(recomputed)
+}
+
+// length of visiting `cities` in `order` and going back to the first one
+pub fn closed_length(order: &[usize], cities: &[(i32, i32)]) -> f32 {
+    order.iter()
+        .zip(order.iter().cycle().skip(1))
+        .map(|(&a, &b)| euclidean_distance(&cities[a], &cities[b]))
+        .sum()
+}
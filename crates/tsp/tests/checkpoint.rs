@@ -0,0 +1,112 @@
+use std::env;
+use std::fs;
+use std::panic::{ self, AssertUnwindSafe };
+use std::process;
+
+use tsp::Config;
+use tsp::checkpoint::{ Checkpoint, checkpointed_edges_swap };
+use tsp::observer::{ Silent, SolverEvent };
+
+fn parse(args: &[&str]) -> Result<Config, &'static str> {
+    Config::new(args.iter().map(|arg| arg.to_string()))
+}
+
+fn config() -> Config {
+    parse(&["200", "1000", "--seed", "7"]).unwrap()
+}
+
+fn temp_path(name: &str) -> String {
+    env::temp_dir().join(format!("tsp-{}-{}", process::id(), name)).to_string_lossy().into_owned()
+}
+
+// a run killed during the pass after the checkpoint of pass `passes` was written
+fn killed_after(passes: usize, path: &str) {
+    let mut checkpoint = Checkpoint::new(&config());
+    let mut kill = |event: &SolverEvent| {
+        if let SolverEvent::Iteration { iteration, .. } = event {
+            assert!(*iteration <= passes, "killed");
+        }
+    };
+    let run = panic::catch_unwind(AssertUnwindSafe(|| checkpointed_edges_swap(&mut checkpoint, path, 1, &mut kill)));
+    assert!(run.is_err());
+}
+
+fn resumed_run_matches_uninterrupted(name: &str) {
+    let (killed, finished) = (temp_path(name), temp_path(&format!("finished-{}", name)));
+
+    let mut uninterrupted = Checkpoint::new(&config());
+    checkpointed_edges_swap(&mut uninterrupted, &finished, 1, &mut Silent).unwrap();
+    assert!(uninterrupted.iteration > 2);
+
+    killed_after(2, &killed);
+    let mut resumed = Checkpoint::load(&killed).unwrap();
+    assert_eq!(resumed.iteration, 2);
+    checkpointed_edges_swap(&mut resumed, &killed, 1, &mut Silent).unwrap();
+
+    assert_eq!(resumed.iteration, uninterrupted.iteration);
+    assert_eq!(resumed.route.indices, uninterrupted.route.indices);
+    assert_eq!(resumed.route.total_distance(), uninterrupted.route.total_distance());
+    // the last checkpoint reads back too, and no partial file is left behind
+    assert_eq!(Checkpoint::load(&killed).unwrap().route.indices, resumed.route.indices);
+    let partial = format!("{}.partial", temp_path(name.split('.').next().unwrap()));
+    let leftovers = fs::read_dir(env::temp_dir()).unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().to_string_lossy().starts_with(&partial))
+        .count();
+    assert_eq!(leftovers, 0);
+
+    fs::remove_file(&killed).unwrap();
+    fs::remove_file(&finished).unwrap();
+}
+
+#[test]
+fn resumed_json_checkpoint_ends_with_the_same_tour() {
+    resumed_run_matches_uninterrupted("json.json");
+    // written as json, not in the binary format
+    let path = temp_path("written.json");
+    Checkpoint::new(&config()).save(&path).unwrap();
+    assert!(fs::read_to_string(&path).unwrap().starts_with('{'));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn resumed_binary_checkpoint_ends_with_the_same_tour() {
+    resumed_run_matches_uninterrupted("binary.bin");
+}
+
+#[test]
+fn checkpoint_is_refused_with_the_other_solvers() {
+    let solvers: [&[&str]; 8] = [
+        &["--graph", "roads.gr"],
+        &["--precedence", "3"],
+        &["--salesmen", "2"],
+        &["--exact"],
+        &["--merge", "4"],
+        &["--partition", "40"],
+        &["--variant", "op"],
+        &["--variant", "pctsp"],
+    ];
+    for solver in solvers {
+        for saved in [["--checkpoint", "ck.json"], ["--resume", "ck.json"]] {
+            let args: Vec<&str> = ["30", "1000"].iter().chain(solver).chain(&saved).cloned().collect();
+            assert_eq!(
+                parse(&args).err(),
+                Some("checkpoint and resume only apply to the plain 2-opt search, without other solver options"),
+                "{:?}", args
+            );
+        }
+        let mut args = vec!["30", "1000"];
+        args.extend_from_slice(solver);
+        assert!(parse(&args).is_ok());
+    }
+    assert!(parse(&["30", "1000", "--checkpoint", "ck.json", "--checkpoint-every", "5"]).is_ok());
+    assert!(parse(&["--resume", "ck.json", "--checkpoint", "other.json"]).is_ok());
+}
+
+#[test]
+fn misspelt_option_is_an_error() {
+    for args in [&["30", "1000", "--checkpont", "ck.json"][..], &["--checkpoint-evry", "5"], &["30", "1000", "1", "--saves"]] {
+        assert_eq!(parse(args).err(), Some("unknown option, see the README for the options"));
+    }
+    assert!(parse(&["30", "1000", "1"]).unwrap().saves);
+}
@@ -0,0 +1,242 @@
+use proptest::prelude::*;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+use tsp::Objective;
+use tsp::algorithms::{ edges_swap, edges_swap_with };
+use tsp::merge::merge_tours;
+use tsp::mtsp::multiple_salesmen;
+use tsp::partition::partitioned_tour;
+use tsp::observer::Silent;
+use tsp::precedence::{ Precedence, generate_pairs, precedence_edges_swap };
+use tsp::prize::{ orienteering, prize_collecting };
+use tsp::route::Route;
+use tsp::validate::{ closed_length, validate_stops, validate_tour };
+
+// length changes smaller than this are float noise
+fn tolerance(length: f32) -> f32 {
+    1e-3 * length.max(1.0)
+}
+
+fn cities(range: std::ops::Range<usize>) -> impl Strategy<Value = Vec<(i32, i32)>> {
+    prop::collection::vec((0..1000i32, 0..1000i32), range)
+}
+
+// every order of the cities after the first one, the shortest closed tour length
+fn brute_force(cities: &[(i32, i32)]) -> f32 {
+    fn permute(order: &mut Vec<usize>, fixed: usize, cities: &[(i32, i32)], best: &mut f32) {
+        if fixed == order.len() {
+            *best = best.min(closed_length(order, cities));
+            return;
+        }
+        for i in fixed..order.len() {
+            order.swap(fixed, i);
+            permute(order, fixed + 1, cities, best);
+            order.swap(fixed, i);
+        }
+    }
+
+    let mut order: Vec<usize> = (0..cities.len()).collect();
+    let mut best = f32::MAX;
+    permute(&mut order, 1, cities, &mut best);
+    best
+}
+
+proptest! {
+    #[test]
+    fn new_route_is_a_valid_tour(cities in cities(1..50)) {
+        let route = Route::new(cities.clone());
+        prop_assert!(validate_tour(&route, &cities).is_ok());
+    }
+
+    #[test]
+    fn swap_edges_keeps_a_permutation(cities in cities(2..50), a in any::<usize>(), b in any::<usize>()) {
+        let mut route = Route::new(cities.clone());
+        route.swap_edges(a % cities.len(), b % cities.len());
+        prop_assert!(validate_tour(&route, &cities).is_ok());
+    }
+
+    #[test]
+    fn move_stop_keeps_a_permutation(cities in cities(2..50), from in any::<usize>(), to in any::<usize>()) {
+        let mut route = Route::new(cities.clone());
+        route.move_stop(from % cities.len(), to % cities.len());
+        prop_assert!(validate_tour(&route, &cities).is_ok());
+    }
+
+    #[test]
+    fn remove_and_insert_keep_a_permutation(cities in cities(2..50), position in any::<usize>()) {
+        let mut route = Route::new(cities.clone());
+        let (city, index) = route.remove_at(position % cities.len());
+        prop_assert!(validate_stops(&route, &cities).is_ok());
+
+        route.insert_cheapest(city, index);
+        prop_assert!(validate_tour(&route, &cities).is_ok());
+    }
+
+    #[test]
+    fn should_edges_swap_never_lengthens(cities in cities(4..50), a in any::<usize>(), b in any::<usize>()) {
+        let mut route = Route::new(cities.clone());
+        let before = route.total_distance();
+        route.should_edges_swap(a % cities.len(), b % cities.len());
+
+        prop_assert!(validate_tour(&route, &cities).is_ok());
+        prop_assert!(route.total_distance() <= before + tolerance(before));
+    }
+
+    #[test]
+    fn optimise_around_never_lengthens(cities in cities(4..50), position in any::<usize>(), radius in 0..10usize) {
+        let mut route = Route::new(cities.clone());
+        let before = route.total_distance();
+        route.optimise_around(position % cities.len(), radius);
+
+        prop_assert!(validate_tour(&route, &cities).is_ok());
+        prop_assert!(route.total_distance() <= before + tolerance(before));
+    }
+
+    #[test]
+    fn edges_swap_never_lengthens(cities in cities(4..40), seed in any::<u64>()) {
+        let mut route = Route::new(cities.clone());
+        let before = route.total_distance();
+        edges_swap(&mut route, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let after = validate_tour(&route, &cities);
+        prop_assert!(after.is_ok());
+        prop_assert!(after.unwrap() <= before + tolerance(before));
+    }
+
+    #[test]
+    fn every_strategy_never_lengthens(cities in cities(4..40), strategy in 0..4usize, seed in any::<u64>()) {
+        // proptest has its own `Strategy`
+        use tsp::Strategy::*;
+        let strategy = [Shuffled, First, Best, Neighbour][strategy];
+        let mut route = Route::new(cities.clone());
+        let before = route.total_distance();
+        let passes = edges_swap_with(&mut route, strategy, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let after = validate_tour(&route, &cities);
+        prop_assert!(passes >= 1);
+        prop_assert!(after.is_ok());
+        prop_assert!(after.unwrap() <= before + tolerance(before));
+    }
+
+    #[test]
+    fn edges_swap_is_optimal_on_tiny_instances(cities in cities(3..5), seed in any::<u64>()) {
+        let mut route = Route::new(cities.clone());
+        edges_swap(&mut route, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let optimal = brute_force(&cities);
+        prop_assert!((route.total_distance() - optimal).abs() <= tolerance(optimal));
+    }
+
+    #[test]
+    fn precedence_moves_keep_the_constraints(cities in cities(4..30), pairs in 1..5usize, seed in any::<u64>()) {
+        let mut rng = Pcg64::seed_from_u64(seed);
+        let precedence = Precedence::new(generate_pairs(cities.len(), pairs, &mut rng));
+        let mut route = precedence.feasible_route(&cities).unwrap();
+        let before = route.total_distance();
+        precedence_edges_swap(&mut route, &precedence, &mut rng, &mut Silent).unwrap();
+
+        prop_assert!(validate_tour(&route, &cities).is_ok());
+        prop_assert!(precedence.validate(&route).is_ok());
+        prop_assert!(route.total_distance() <= before + tolerance(before));
+    }
+
+    #[test]
+    fn salesmen_share_every_city_once(cities in cities(2..40), salesmen in 1..12usize, max in any::<bool>(), seed in any::<u64>()) {
+        let objective = if max { Objective::MinMax } else { Objective::MinSum };
+        let tours = multiple_salesmen(&cities, salesmen, objective, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+        if salesmen >= cities.len() {
+            prop_assert!(tours.is_err());
+            return Ok(());
+        }
+        let tours = tours.unwrap();
+        prop_assert_eq!(tours.len(), salesmen);
+
+        let mut visited: Vec<usize> = vec![];
+        for tour in &tours {
+            prop_assert!(validate_stops(tour, &cities).is_ok());
+            prop_assert!(tour.indices.contains(&0));
+            prop_assert!(tour.indices.len() >= 2);
+            visited.extend(tour.indices.iter().filter(|&&city| city != 0));
+        }
+        visited.sort_unstable();
+        prop_assert_eq!(visited, (1..cities.len()).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn orienteering_stays_within_budget(cities in cities(2..40), budget in 0.0..4000f32, seed in any::<u64>()) {
+        let prizes: Vec<i32> = (0..cities.len() as i32).map(|city| city % 7 + 1).collect();
+        let report = orienteering(&cities, &prizes, budget, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let length = validate_stops(&report.route, &cities);
+        prop_assert!(length.is_ok());
+        prop_assert!(report.route.indices.contains(&0));
+        prop_assert!(length.unwrap() <= budget + tolerance(budget));
+    }
+
+    #[test]
+    fn prize_collecting_beats_skipping_nothing(cities in cities(2..40), seed in any::<u64>()) {
+        let penalties: Vec<i32> = (0..cities.len() as i32).map(|city| city % 50 + 1).collect();
+        let report = prize_collecting(&cities, &penalties, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let length = validate_stops(&report.route, &cities);
+        prop_assert!(length.is_ok());
+        prop_assert!(report.route.indices.contains(&0));
+
+        let mut full = Route::new(cities.clone());
+        edges_swap(&mut full, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+        let objective = length.unwrap() + report.penalty as f32;
+        prop_assert!(objective <= full.total_distance() + tolerance(objective));
+    }
+
+    #[test]
+    fn merged_tour_is_no_longer_than_its_parents(cities in cities(4..60), starts in 1..5usize, seed in any::<u64>()) {
+        let route = Route::new(cities.clone());
+        let (child, parents) = merge_tours(&route, starts, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let best = parents.iter().map(|parent| parent.total_distance()).fold(f32::MAX, f32::min);
+        let length = validate_tour(&child, &cities);
+        prop_assert!(length.is_ok());
+        prop_assert!(length.unwrap() <= best + tolerance(best));
+    }
+
+    #[test]
+    fn partitioned_tour_is_no_longer_than_stitched(cities in cities(1..300), cell_size in 1..40usize, seed in any::<u64>()) {
+        let route = Route::new(cities.clone());
+        let report = partitioned_tour(&route, cell_size, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let length = validate_tour(&report.route, &cities);
+        prop_assert!(length.is_ok());
+        prop_assert!(length.unwrap() <= report.stitched + tolerance(report.stitched));
+    }
+
+    #[test]
+    fn serialized_route_reads_back_the_same(cities in cities(1..50), seed in any::<u64>()) {
+        let mut route = Route::new(cities.clone());
+        edges_swap(&mut route, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let json: Route = tsp::result::from_json(&tsp::result::to_json(&route).unwrap()).unwrap();
+        let bytes: Route = tsp::result::from_bytes(&tsp::result::to_bytes(&route).unwrap()).unwrap();
+        for read in [json, bytes] {
+            prop_assert_eq!(&read.indices, &route.indices);
+            prop_assert!(validate_tour(&read, &cities).is_ok());
+        }
+    }
+}
+
+#[cfg(feature = "exact")]
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn cutting_plane_matches_brute_force(cities in cities(4..8), seed in any::<u64>()) {
+        let route = Route::new(cities.clone());
+        let report = tsp::exact::cutting_plane(&route, 10_000, &mut Pcg64::seed_from_u64(seed), &mut Silent);
+
+        let optimal = brute_force(&cities);
+        prop_assert!(report.optimal);
+        prop_assert!(validate_tour(&report.route, &cities).is_ok());
+        prop_assert!((report.route.total_distance() - optimal).abs() <= tolerance(optimal));
+        prop_assert!(report.lower_bound as f32 <= optimal + tolerance(optimal));
+    }
+}